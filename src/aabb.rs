@@ -0,0 +1,66 @@
+use crate::*;
+
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub minimum: Vec3d,
+    pub maximum: Vec3d,
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self {
+        minimum: Vec3d::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        maximum: Vec3d::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+    };
+
+    pub const fn new(minimum: Vec3d, maximum: Vec3d) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn surrounding(self, other: Self) -> Self {
+        Self {
+            minimum: self.minimum.min(other.minimum),
+            maximum: self.maximum.max(other.maximum),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3d {
+        (self.minimum + self.maximum) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.maximum - self.minimum;
+        if extent.min_component() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    // returns the distance at which the ray enters the box, clamped to t_min
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        self.intersect(ray, t_min, t_max)
+            .map(|(t_enter, _)| t_enter)
+    }
+
+    // returns the distances at which the ray enters and leaves the box, clamped to [t_min, t_max]
+    pub fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let mut t_enter = t_min;
+        let mut t_exit = t_max;
+
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let mut t0 = (self.minimum[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.maximum[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_enter = if t0 > t_enter { t0 } else { t_enter };
+            t_exit = if t1 < t_exit { t1 } else { t_exit };
+            if t_exit < t_enter {
+                return None;
+            }
+        }
+
+        Some((t_enter, t_exit))
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use crate::*;
+
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+const MAX_LEAF_SIZE: usize = 4;
+
+pub struct BvhNode {
+    bounding_box: Aabb,
+    contents: BvhContents,
+}
+
+enum BvhContents {
+    Leaf(Vec<Arc<dyn Hittable>>),
+    Split(Box<BvhNode>, Box<BvhNode>),
+}
+
+struct BuildEntry {
+    bounding_box: Aabb,
+    centroid: Vec3d,
+    object: Arc<dyn Hittable>,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        let entries = list
+            .into_objects()
+            .into_iter()
+            .map(|object| {
+                let bounding_box = object.bounding_box();
+                BuildEntry {
+                    bounding_box,
+                    centroid: bounding_box.centroid(),
+                    object,
+                }
+            })
+            .collect();
+
+        Self::build(entries)
+    }
+
+    fn build(mut entries: Vec<BuildEntry>) -> Self {
+        let bounding_box = entries.iter().fold(Aabb::EMPTY, |acc, entry| {
+            acc.surrounding(entry.bounding_box)
+        });
+
+        if entries.len() <= 1 {
+            return Self::leaf(bounding_box, entries);
+        }
+
+        let (axis, split, split_cost) = Self::find_sah_split(&mut entries, &bounding_box);
+        let leaf_cost = INTERSECTION_COST * entries.len() as f64;
+
+        if entries.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            return Self::leaf(bounding_box, entries);
+        }
+
+        Self::sort_by_axis(&mut entries, axis);
+        let right = entries.split_off(split);
+
+        Self {
+            bounding_box,
+            contents: BvhContents::Split(
+                Box::new(Self::build(entries)),
+                Box::new(Self::build(right)),
+            ),
+        }
+    }
+
+    fn leaf(bounding_box: Aabb, entries: Vec<BuildEntry>) -> Self {
+        Self {
+            bounding_box,
+            contents: BvhContents::Leaf(entries.into_iter().map(|entry| entry.object).collect()),
+        }
+    }
+
+    // sweeps every axis and returns the axis, split index and SAH cost of the cheapest partition
+    fn find_sah_split(entries: &mut [BuildEntry], bounding_box: &Aabb) -> (usize, usize, f64) {
+        let count = entries.len();
+        let parent_area = bounding_box.surface_area();
+
+        let mut best = (0, count / 2, f64::INFINITY);
+        let mut right_areas = vec![0.0; count];
+
+        for axis in 0..3 {
+            Self::sort_by_axis(entries, axis);
+
+            let mut right_box = Aabb::EMPTY;
+            for i in (1..count).rev() {
+                right_box = right_box.surrounding(entries[i].bounding_box);
+                right_areas[i] = right_box.surface_area();
+            }
+
+            let mut left_box = Aabb::EMPTY;
+            for i in 1..count {
+                left_box = left_box.surrounding(entries[i - 1].bounding_box);
+                let left_count = i as f64;
+                let right_count = (count - i) as f64;
+
+                let cost = if parent_area > 0.0 {
+                    TRAVERSAL_COST
+                        + INTERSECTION_COST
+                            * (left_box.surface_area() * left_count + right_areas[i] * right_count)
+                            / parent_area
+                } else {
+                    TRAVERSAL_COST + INTERSECTION_COST * count as f64
+                };
+
+                if cost < best.2 {
+                    best = (axis, i, cost);
+                }
+            }
+        }
+
+        best
+    }
+
+    fn sort_by_axis(entries: &mut [BuildEntry], axis: usize) {
+        entries.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    }
+
+    fn hit_contents(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut sampler: Option<&mut (dyn Sampler + '_)>,
+    ) -> Option<HitRecord<'_>> {
+        match &self.contents {
+            BvhContents::Leaf(objects) => closest_hit(objects, ray, t_min, t_max, sampler),
+            BvhContents::Split(left, right) => {
+                let left_t = left.bounding_box.hit(ray, t_min, t_max);
+                let right_t = right.bounding_box.hit(ray, t_min, t_max);
+
+                // visit the nearer child first so the far one can be culled by the closest hit
+                let (near, far, far_t) = match (left_t, right_t) {
+                    (None, None) => return None,
+                    (Some(_), None) => return left.hit_contents(ray, t_min, t_max, sampler),
+                    (None, Some(_)) => return right.hit_contents(ray, t_min, t_max, sampler),
+                    (Some(l), Some(r)) if r < l => (right, left, l),
+                    (Some(_), Some(r)) => (left, right, r),
+                };
+
+                let near_hit = near.hit_contents(ray, t_min, t_max, sampler.as_deref_mut());
+                let closest_so_far = near_hit.as_ref().map_or(t_max, |hit_record| hit_record.t);
+
+                if far_t > closest_so_far {
+                    return near_hit;
+                }
+
+                far.hit_contents(ray, t_min, closest_so_far, sampler)
+                    .or(near_hit)
+            }
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bounding_box.hit(ray, t_min, t_max)?;
+        self.hit_contents(ray, t_min, t_max, None)
+    }
+
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        self.bounding_box.hit(ray, t_min, t_max)?;
+        self.hit_contents(ray, t_min, t_max, Some(sampler))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vec3d(low: f64, high: f64) -> Vec3d {
+        Vec3d::new(
+            gen_random_range(low, high),
+            gen_random_range(low, high),
+            gen_random_range(low, high),
+        )
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_rays() {
+        seed_thread_rng(7);
+
+        let mut objects: Vec<Arc<dyn Hittable>> = Vec::new();
+        for _ in 0..100 {
+            let material = Box::new(Lambertian::new(Colour::fill(0.5)));
+            objects.push(Arc::new(Sphere::new(
+                random_vec3d(-10.0, 10.0),
+                gen_random_range(0.1, 1.5),
+                material,
+            )));
+        }
+        for _ in 0..20 {
+            let material = Box::new(Lambertian::new(Colour::fill(0.5)));
+            objects.push(Arc::new(
+                Quad::new(
+                    random_vec3d(-10.0, 10.0),
+                    random_vec3d(-2.0, 2.0),
+                    random_vec3d(-2.0, 2.0),
+                    material,
+                )
+                .unwrap(),
+            ));
+        }
+
+        let mut list = HittableList::new();
+        let mut bvh_list = HittableList::new();
+        for object in objects {
+            list.add(object.clone());
+            bvh_list.add(object);
+        }
+        let bvh = BvhNode::new(bvh_list);
+
+        for _ in 0..10_000 {
+            let origin = random_vec3d(-15.0, 15.0);
+            let direction = random_vec3d(-1.0, 1.0);
+            let ray = Ray::new(origin, direction, 0.0);
+            let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+            let actual = bvh.hit(&ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+            assert_eq!(
+                actual, expected,
+                "origin {origin:?}, direction {direction:?}"
+            );
+        }
+    }
+}
@@ -1,64 +1,662 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
 use crate::*;
 
-pub struct Camera {
+// `u` runs left to right and `v` bottom to top across the image, both from 0 to 1; pixels the
+// projection does not cover get no ray and stay black
+pub trait Camera: Send + Sync {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray>;
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // parallel rays over a view `height` world units tall
+    Orthographic {
+        height: f64,
+    },
+    // equidistant fisheye with `fov` degrees across the image circle, which spans the image height
+    Fisheye {
+        #[serde(default = "default_fisheye_fov")]
+        fov: f64,
+    },
+    // the whole sphere of directions, longitude across the image and latitude up it
+    Equirectangular,
+}
+
+fn default_fisheye_fov() -> f64 {
+    180.0
+}
+
+// the controls of a real camera, with scene units taken to be metres and radiance in nits
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicalCamera {
+    // in millimetres
+    pub focal_length: f64,
+    pub f_number: f64,
+    pub iso: f64,
+    // seconds the shutter stays open
+    pub shutter_speed: f64,
+    // in millimetres, full frame by default
+    #[serde(default = "default_sensor_height")]
+    pub sensor_height: f64,
+}
+
+fn default_sensor_height() -> f64 {
+    24.0
+}
+
+impl PhysicalCamera {
+    // in degrees, for a perspective projection
+    pub fn vfov(&self) -> f64 {
+        2.0 * (self.sensor_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    // diameter of the lens opening in metres
+    pub fn aperture(&self) -> f64 {
+        self.focal_length / self.f_number / 1000.0
+    }
+
+    // scales radiance so the brightest luminance the sensor records without clipping maps to one,
+    // using the saturation based ISO speed with the usual lens and vignetting factor of 0.65
+    pub fn exposure(&self) -> f64 {
+        let saturation_luminance =
+            78.0 / (0.65 * self.iso) * self.f_number * self.f_number / self.shutter_speed;
+        1.0 / saturation_luminance
+    }
+}
+
+// the outline of the lens opening, which out of focus highlights take the shape of
+#[derive(Clone, Default)]
+pub enum ApertureShape {
+    #[default]
+    Circle,
+    // a regular polygon with one corner per blade, turned by `rotation` radians
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl ApertureShape {
+    // a point uniform over the opening, which fits inside the unit disk
+    fn sample(&self, sampler: &mut dyn Sampler) -> (f64, f64) {
+        match self {
+            Self::Circle => concentric_disk(sampler.get_2d()),
+            Self::Polygon { blades, rotation } => {
+                // the polygon is a fan of equal triangles around the centre, so pick one of them
+                let (random_blade, random_point) = sampler.get_2d();
+                let position = random_blade * *blades as f64;
+                let blade = position.floor().min((*blades - 1) as f64);
+                let corner_angle = |index: f64| rotation + 2.0 * PI * index / *blades as f64;
+                let (sin_first, cos_first) = corner_angle(blade).sin_cos();
+                let (sin_second, cos_second) = corner_angle(blade + 1.0).sin_cos();
+
+                let root = random_point.sqrt();
+                let first = root * (1.0 - (position - blade));
+                let second = root * (position - blade);
+                (
+                    first * cos_first + second * cos_second,
+                    first * sin_first + second * sin_second,
+                )
+            }
+            Self::Mask(mask) => mask.sample(sampler.get_2d()),
+        }
+    }
+}
+
+// an image of the opening; brighter texels let through more light, so they are sampled more often
+pub struct ApertureMask {
+    distribution: Distribution2D,
+    // half extents of the image once its longer side is fitted to the unit disk's diameter
+    scale_x: f64,
+    scale_y: f64,
+}
+
+impl ApertureMask {
+    pub fn new(image: &Image) -> Self {
+        let transmission: Vec<f64> = image
+            .pixels
+            .iter()
+            .map(|pixel| 0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z)
+            .collect();
+        let longest_side = image.width.max(image.height) as f64;
+
+        Self {
+            distribution: Distribution2D::new(&transmission, image.width, image.height),
+            scale_x: image.width as f64 / longest_side,
+            scale_y: image.height as f64 / longest_side,
+        }
+    }
+
+    // the image's top row is the top of the aperture
+    fn sample(&self, random: (f64, f64)) -> (f64, f64) {
+        let (column, row) = self.distribution.sample(random.0, random.1);
+        (
+            (2.0 * column - 1.0) * self.scale_x,
+            (1.0 - 2.0 * row) * self.scale_y,
+        )
+    }
+}
+
+// a thin lens `aperture` wide, in front of which everything at the focus distance is sharp
+#[derive(Clone)]
+pub struct Lens {
+    radius: f64,
+    shape: ApertureShape,
+}
+
+impl Lens {
+    pub const fn new(aperture: f64, shape: ApertureShape) -> Self {
+        Self {
+            radius: aperture / 2.0,
+            shape,
+        }
+    }
+
+    // a point on the lens relative to its centre
+    fn offset(&self, frame: &CameraFrame, sampler: &mut dyn Sampler) -> Vec3d {
+        let (lens_x, lens_y) = self.shape.sample(sampler);
+        frame.u * (self.radius * lens_x) + frame.v * (self.radius * lens_y)
+    }
+}
+
+#[derive(Clone)]
+pub struct CameraSettings {
+    pub look_from: Vec3d,
+    pub look_at: Vec3d,
+    pub up_vector: Vec3d,
+    pub projection: Projection,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub aperture_shape: ApertureShape,
+    pub focus_distance: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // replaces the pose, field of view and focus distance above when present
+    pub animation: Option<CameraAnimation>,
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64, frame_number: f64) -> Box<dyn Camera> {
+        let (frame, vfov, focus_distance) = match &self.animation {
+            Some(animation) => {
+                let keyframe = animation.at(frame_number);
+                (
+                    CameraFrame::from_orientation(keyframe.position, keyframe.orientation),
+                    keyframe.vfov,
+                    keyframe.focus_distance,
+                )
+            }
+            None => (
+                CameraFrame::new(self.look_from, self.look_at, self.up_vector),
+                self.vfov,
+                self.focus_distance,
+            ),
+        };
+        let frame = frame.with_shutter(self.shutter_open, self.shutter_close);
+        let lens = Lens::new(self.aperture, self.aperture_shape.clone());
+        match self.projection {
+            Projection::Perspective => Box::new(PerspectiveCamera::new(
+                frame,
+                vfov,
+                aspect_ratio,
+                lens,
+                focus_distance,
+            )),
+            Projection::Orthographic { height } => Box::new(OrthographicCamera::new(
+                frame,
+                height,
+                aspect_ratio,
+                lens,
+                focus_distance,
+            )),
+            Projection::Fisheye { fov } => Box::new(FisheyeCamera::new(frame, fov, aspect_ratio)),
+            Projection::Equirectangular => Box::new(EquirectangularCamera::new(frame)),
+        }
+    }
+}
+
+// where the camera is and how it is set up at one frame of an animation
+#[derive(Clone, Copy)]
+pub struct CameraKeyframe {
+    pub frame: f64,
+    pub position: Vec3d,
+    // the rotation `CameraFrame::from_orientation` expects
+    pub orientation: QuatDouble,
+    pub vfov: f64,
+    pub focus_distance: f64,
+}
+
+// position, field of view and focus distance follow Catmull-Rom splines through the keyframes
+// while the orientation is slerped; frames outside the keyframes hold the first or last one
+#[derive(Clone)]
+pub struct CameraAnimation {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraAnimation {
+    pub fn new(keyframes: Vec<CameraKeyframe>) -> Result<Self, String> {
+        if keyframes.is_empty() {
+            return Err("an animation needs at least one keyframe".to_string());
+        }
+        if keyframes
+            .windows(2)
+            .any(|pair| pair[0].frame.partial_cmp(&pair[1].frame) != Some(std::cmp::Ordering::Less))
+        {
+            return Err("keyframes must be in increasing frame order".to_string());
+        }
+        Ok(Self { keyframes })
+    }
+
+    pub fn at(&self, frame: f64) -> CameraKeyframe {
+        let last = self.keyframes.len() - 1;
+        if last == 0 {
+            return self.keyframes[0];
+        }
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame <= frame)
+            .clamp(1, last);
+
+        let indices = [next.saturating_sub(2), next - 1, next, (next + 1).min(last)];
+        let keys = indices.map(|index| self.keyframes[index]);
+        let times = keys.map(|keyframe| keyframe.frame);
+        let progress = ((frame - times[1]) / (times[2] - times[1])).clamp(0.0, 1.0);
+
+        CameraKeyframe {
+            frame,
+            position: catmull_rom(keys.map(|keyframe| keyframe.position), times, progress),
+            orientation: keys[1].orientation.slerp(keys[2].orientation, progress),
+            vfov: catmull_rom(keys.map(|keyframe| keyframe.vfov), times, progress),
+            focus_distance: catmull_rom(
+                keys.map(|keyframe| keyframe.focus_distance),
+                times,
+                progress,
+            ),
+        }
+    }
+}
+
+// the segment from `points[1]` to `points[2]` as a cubic Hermite curve, with tangents taken from
+// the neighbours on either side and scaled by the keyframe spacing so uneven gaps do not overshoot
+fn catmull_rom<T>(points: [T; 4], times: [f64; 4], progress: f64) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let duration = times[2] - times[1];
+    let tangent = |before: usize, after: usize| {
+        (points[after] - points[before]) * (duration / (times[after] - times[before]))
+    };
+    let start_tangent = tangent(0, 2);
+    let end_tangent = tangent(1, 3);
+
+    let s = progress;
+    let s2 = s * s;
+    let s3 = s2 * s;
+    points[1] * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + start_tangent * (s3 - 2.0 * s2 + s)
+        + points[2] * (3.0 * s2 - 2.0 * s3)
+        + end_tangent * (s3 - s2)
+}
+
+// where a camera sits, its axes, and when its shutter is open; `w` points away from the view
+#[derive(Clone, Copy)]
+pub struct CameraFrame {
     origin: Vec3d,
+    u: Vec3d,
+    v: Vec3d,
+    w: Vec3d,
+    shutter_open: f64,
+    shutter_close: f64,
+}
+
+impl CameraFrame {
+    pub fn new(look_from: Vec3d, look_at: Vec3d, up_vector: Vec3d) -> Self {
+        let w = (look_from - look_at).normalise();
+        let u = up_vector.cross(w).normalise();
+        let v = w.cross(u);
+
+        Self {
+            origin: look_from,
+            u,
+            v,
+            w,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    // `orientation` turns the camera's right, up and backward axes from x, y and z into the world
+    pub fn from_orientation(position: Vec3d, orientation: QuatDouble) -> Self {
+        Self {
+            origin: position,
+            u: orientation.rotate_vector3(Vec3d::X),
+            v: orientation.rotate_vector3(Vec3d::Y),
+            w: orientation.rotate_vector3(Vec3d::Z),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    // rays are spread evenly over the time the shutter is open, blurring anything that moves
+    pub fn with_shutter(self, shutter_open: f64, shutter_close: f64) -> Self {
+        Self {
+            shutter_open,
+            shutter_close,
+            ..self
+        }
+    }
+
+    fn time(&self, sampler: &mut dyn Sampler) -> f64 {
+        self.shutter_open + sampler.get_1d() * (self.shutter_close - self.shutter_open)
+    }
+
+    // turns a direction given as right, up and forward components into world space
+    fn direction(&self, right: f64, up: f64, forward: f64) -> Vec3d {
+        self.u * right + self.v * up - self.w * forward
+    }
+}
+
+pub struct PerspectiveCamera {
+    frame: CameraFrame,
     lower_left_corner: Vec3d,
     horizontal: Vec3d,
     vertical: Vec3d,
-    u: Vec3d,
-    v: Vec3d,
-    lens_radius: f64,
+    lens: Lens,
 }
 
-impl Camera {
+impl PerspectiveCamera {
     pub fn new(
-        look_from: Vec3d,
-        look_at: Vec3d,
-        up_vector: Vec3d,
+        frame: CameraFrame,
         vfov: f64,
-        aperture: f64,
+        aspect_ratio: f64,
+        lens: Lens,
         focus_distance: f64,
     ) -> Self {
         let theta = vfov.to_radians();
         let h = (theta / 2.0).tan();
 
         let viewport_height: f64 = 2.0 * h;
-        let viewport_width: f64 = viewport_height * ASPECT_RATIO;
+        let viewport_width: f64 = viewport_height * aspect_ratio;
 
-        let w = (look_from - look_at).normalise();
-        let u = up_vector.cross(w).normalise();
-        let v = w.cross(u);
+        let horizontal = focus_distance * viewport_width * frame.u;
+        let vertical = focus_distance * viewport_height * frame.v;
+        let lower_left_corner =
+            frame.origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * frame.w;
 
-        let origin = look_from;
-        let horizontal = focus_distance * viewport_width * u;
-        let vertical = focus_distance * viewport_height * v;
-        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * w;
+        Self {
+            frame,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens,
+        }
+    }
+}
 
-        let lens_radius = aperture / 2.0;
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let offset = self.lens.offset(&self.frame, sampler);
+        let origin = self.frame.origin + offset;
 
-        Self {
+        Some(Ray::new(
             origin,
+            self.lower_left_corner + self.horizontal * u + self.vertical * v - origin,
+            self.frame.time(sampler),
+        ))
+    }
+}
+
+// parallel projection, so sizes do not shrink with distance; a non-zero aperture still blurs
+// everything away from the focus plane
+pub struct OrthographicCamera {
+    frame: CameraFrame,
+    lower_left_corner: Vec3d,
+    horizontal: Vec3d,
+    vertical: Vec3d,
+    lens: Lens,
+    focus_distance: f64,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        frame: CameraFrame,
+        height: f64,
+        aspect_ratio: f64,
+        lens: Lens,
+        focus_distance: f64,
+    ) -> Self {
+        let horizontal = height * aspect_ratio * frame.u;
+        let vertical = height * frame.v;
+        let lower_left_corner = frame.origin - horizontal / 2.0 - vertical / 2.0;
+
+        Self {
+            frame,
             lower_left_corner,
             horizontal,
             vertical,
-            u,
-            v,
-            lens_radius,
+            lens,
+            focus_distance,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let centre = self.lower_left_corner + self.horizontal * u + self.vertical * v;
+        let focus_point = centre - self.frame.w * self.focus_distance;
+        let origin = centre + self.lens.offset(&self.frame, sampler);
+
+        Some(Ray::new(
+            origin,
+            focus_point - origin,
+            self.frame.time(sampler),
+        ))
+    }
+}
+
+// the angle from the view direction grows in proportion to the distance from the image centre
+pub struct FisheyeCamera {
+    frame: CameraFrame,
+    half_fov: f64,
+    aspect_ratio: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(frame: CameraFrame, fov: f64, aspect_ratio: f64) -> Self {
+        Self {
+            frame,
+            half_fov: fov.to_radians() / 2.0,
+            aspect_ratio,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let x = (2.0 * u - 1.0) * self.aspect_ratio;
+        let y = 2.0 * v - 1.0;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
         }
+
+        let theta = radius * self.half_fov;
+        let phi = y.atan2(x);
+        let direction = self.frame.direction(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+        Some(Ray::new(
+            self.frame.origin,
+            direction,
+            self.frame.time(sampler),
+        ))
+    }
+}
+
+// a full 360 by 180 degree panorama with the view direction in the centre, best at a 2:1 aspect
+pub struct EquirectangularCamera {
+    frame: CameraFrame,
+}
+
+impl EquirectangularCamera {
+    pub const fn new(frame: CameraFrame) -> Self {
+        Self { frame }
     }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = (v - 0.5) * PI;
+        let direction = self.frame.direction(
+            theta.cos() * phi.sin(),
+            theta.sin(),
+            theta.cos() * phi.cos(),
+        );
+        Some(Ray::new(
+            self.frame.origin,
+            direction,
+            self.frame.time(sampler),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let rd = self.lens_radius
-            * Vec3d::new(
-                rand::thread_rng().gen_range(-1.0..1.0),
-                rand::thread_rng().gen_range(-1.0..1.0),
+    fn keyframe(frame: f64, position: Vec3d, forward: Vec3d, vfov: f64) -> CameraKeyframe {
+        CameraKeyframe {
+            frame,
+            position,
+            orientation: QuatDouble::from_forward_up(forward.normalise(), Vec3d::Y),
+            vfov,
+            focus_distance: 10.0 + frame,
+        }
+    }
+
+    fn keyframes() -> Vec<CameraKeyframe> {
+        vec![
+            keyframe(
                 0.0,
-            );
-        let offset = self.u * rd.x + self.v * rd.y;
+                Vec3d::new(0.0, 1.0, 5.0),
+                Vec3d::new(0.0, 0.0, -1.0),
+                40.0,
+            ),
+            keyframe(
+                10.0,
+                Vec3d::new(4.0, 2.0, 3.0),
+                Vec3d::new(-1.0, 0.0, -1.0),
+                30.0,
+            ),
+            keyframe(
+                15.0,
+                Vec3d::new(6.0, 1.0, -2.0),
+                Vec3d::new(-1.0, -0.2, 0.0),
+                50.0,
+            ),
+            keyframe(
+                30.0,
+                Vec3d::new(0.0, 3.0, -6.0),
+                Vec3d::new(0.0, -0.5, 1.0),
+                45.0,
+            ),
+        ]
+    }
+
+    fn assert_matches(actual: CameraKeyframe, expected: CameraKeyframe) {
+        assert!((actual.position - expected.position).length() < 1.0e-9);
+        let forward = |keyframe: CameraKeyframe| keyframe.orientation.rotate_vector3(-Vec3d::Z);
+        assert!((forward(actual) - forward(expected)).length() < 1.0e-9);
+        assert!((actual.vfov - expected.vfov).abs() < 1.0e-9);
+        assert!((actual.focus_distance - expected.focus_distance).abs() < 1.0e-9);
+    }
 
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin - offset,
+    #[test]
+    fn animation_passes_through_its_keyframes() {
+        let animation = CameraAnimation::new(keyframes()).unwrap();
+        for keyframe in keyframes() {
+            assert_matches(animation.at(keyframe.frame), keyframe);
+        }
+    }
+
+    #[test]
+    fn animation_holds_the_end_keyframes_outside_its_range() {
+        let keyframes = keyframes();
+        let animation = CameraAnimation::new(keyframes.clone()).unwrap();
+        assert_matches(animation.at(-5.0), keyframes[0]);
+        assert_matches(animation.at(100.0), keyframes[3]);
+
+        let still = CameraAnimation::new(vec![keyframes[1]]).unwrap();
+        assert_matches(still.at(0.0), keyframes[1]);
+        assert_matches(still.at(50.0), keyframes[1]);
+    }
+
+    #[test]
+    fn animation_needs_increasing_keyframes() {
+        assert!(CameraAnimation::new(vec![]).is_err());
+
+        let mut keyframes = keyframes();
+        keyframes.swap(1, 2);
+        assert!(CameraAnimation::new(keyframes.clone()).is_err());
+
+        keyframes[2] = keyframes[1];
+        assert!(CameraAnimation::new(keyframes).is_err());
+    }
+
+    #[test]
+    fn physical_camera_matches_known_values() {
+        // a 50 mm lens on full frame film, at the sunny sixteen exposure for ISO 100
+        let camera: PhysicalCamera = toml::from_str(
+            "focal_length = 50.0\nf_number = 16.0\niso = 100.0\nshutter_speed = 0.01\n",
         )
+        .unwrap();
+        assert_eq!(camera.sensor_height, 24.0);
+
+        // the 27 by 39.6 degree view every 50 mm lens guide lists
+        assert!((camera.vfov() - 26.9915).abs() < 1.0e-4);
+        let across = PhysicalCamera {
+            sensor_height: 36.0,
+            ..camera
+        };
+        assert!((across.vfov() - 39.5978).abs() < 1.0e-4);
+
+        // 50 / 16 mm across
+        assert!((camera.aperture() - 0.003125).abs() < 1.0e-12);
+
+        // the sensor saturates at 78 / (0.65 * 100) * 16^2 / 0.01 = 30720 nits
+        assert!((camera.exposure() * 30720.0 - 1.0).abs() < 1.0e-12);
+
+        // a stop more light from each control doubles the exposure
+        for brighter in [
+            PhysicalCamera {
+                iso: 200.0,
+                ..camera
+            },
+            PhysicalCamera {
+                shutter_speed: 0.02,
+                ..camera
+            },
+            PhysicalCamera {
+                f_number: 16.0 / 2.0f64.sqrt(),
+                ..camera
+            },
+        ] {
+            assert!((brighter.exposure() / camera.exposure() - 2.0).abs() < 1.0e-12);
+        }
     }
 }
@@ -0,0 +1,202 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::*;
+
+#[derive(Parser)]
+#[command(version, about = "Renders a scene with a Monte Carlo path tracer")]
+pub struct Cli {
+    /// Built-in scene (`random_world`, `cornell_box`, `cornell_meshes`), an OBJ model or a TOML scene file
+    pub scene: Option<String>,
+
+    /// Where to write the rendered image
+    #[arg(short, long, default_value = "image.ppm")]
+    pub output: PathBuf,
+
+    /// Image format, guessed from the output extension when omitted
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image height in pixels, derived from the aspect ratio when omitted
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "aspect_ratio")]
+    pub height: Option<u32>,
+
+    /// Width divided by height, e.g. `1.5` or `16:9`
+    #[arg(long, value_parser = parse_aspect_ratio)]
+    pub aspect_ratio: Option<f64>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: Option<u32>,
+
+    /// Maximum number of bounces per path
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<u32>,
+
+    /// Bounces before paths start being terminated by Russian roulette
+    #[arg(long)]
+    pub russian_roulette_depth: Option<u32>,
+
+    /// Seed for every random number used while rendering, including the layout of `random_world`
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// How the random numbers for each pixel are generated
+    #[arg(long, value_enum)]
+    pub sampler: Option<SamplerKind>,
+
+    /// Frames of the camera animation to render, e.g. `1:120`, numbering each output file
+    #[arg(long, value_parser = parse_frame_range)]
+    pub frames: Option<RangeInclusive<u32>>,
+
+    /// Worker threads, defaults to the number of available cores
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(':') {
+        Some((width, height)) => {
+            let width: f64 = width
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{width}` is not a number"))?;
+            let height: f64 = height
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{height}` is not a number"))?;
+            width / height
+        }
+        None => value
+            .parse()
+            .map_err(|_| format!("`{value}` is not a number or a `width:height` ratio"))?,
+    };
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("`{value}` is not a positive aspect ratio"))
+    }
+}
+
+fn parse_frame_range(value: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse = |frame: &str| {
+        frame
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("`{frame}` is not a frame number"))
+    };
+    let (first, last) = match value.split_once(':') {
+        Some((first, last)) => (parse(first)?, parse(last)?),
+        None => (parse(value)?, parse(value)?),
+    };
+
+    if first <= last {
+        Ok(first..=last)
+    } else {
+        Err(format!("`{value}` ends before it starts"))
+    }
+}
+
+impl Cli {
+    pub fn output_format(&self) -> Result<OutputFormat, String> {
+        self.format
+            .or_else(|| OutputFormat::from_extension(&self.output))
+            .ok_or_else(|| {
+                format!(
+                    "cannot tell the image format of `{}`, pass --format",
+                    self.output.display()
+                )
+            })
+    }
+
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads as usize,
+            None => std::thread::available_parallelism().map_or(1, |count| count.get()),
+        }
+    }
+
+    // overrides the scene's render settings with any flags given on the command line
+    pub fn apply(&self, settings: &mut RenderSettings) -> Result<(), String> {
+        let aspect_ratio = self.aspect_ratio.unwrap_or(settings.aspect_ratio());
+
+        if let Some(width) = self.width {
+            settings.image_width = width;
+        }
+        settings.image_height = match self.height {
+            Some(height) => height,
+            None if self.width.is_some() || self.aspect_ratio.is_some() => {
+                (settings.image_width as f64 / aspect_ratio) as u32
+            }
+            None => settings.image_height,
+        };
+
+        if let Some(samples) = self.samples {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(russian_roulette_depth) = self.russian_roulette_depth {
+            settings.russian_roulette_depth = russian_roulette_depth;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+
+        settings.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aspect_ratios() {
+        let accepted = [
+            ("1.5", 1.5),
+            ("16:9", 16.0 / 9.0),
+            (" 4 : 3 ", 4.0 / 3.0),
+            ("1:1", 1.0),
+        ];
+        for (value, expected) in accepted {
+            assert_eq!(parse_aspect_ratio(value), Ok(expected), "{value}");
+        }
+
+        let rejected = [
+            "", "0", "-1.5", "0:1", "1:0", "-16:9", "a:b", "16:", "inf", "NaN", "1:2:3",
+        ];
+        for value in rejected {
+            assert!(parse_aspect_ratio(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn frame_ranges() {
+        let accepted = [
+            ("7", 7..=7),
+            ("1:120", 1..=120),
+            ("0:0", 0..=0),
+            (" 2 : 5 ", 2..=5),
+        ];
+        for (value, expected) in accepted {
+            assert_eq!(parse_frame_range(value), Ok(expected), "{value}");
+        }
+
+        let rejected = ["", "5:2", "5..2", "1-3", "-1:3", "a:b", "1:", "1.5:3"];
+        for value in rejected {
+            assert!(parse_frame_range(value).is_err(), "{value}");
+        }
+    }
+}
@@ -5,7 +5,8 @@ use spyder_math::*;
 pub type Colour = Vec3d;
 
 pub trait WritePpm {
-    fn write_ppm(&self, file: &mut impl Write, samples: u32);
+    fn to_rgb8(&self, scale: f64) -> [u8; 3];
+    fn write_ppm(&self, file: &mut impl Write, scale: f64);
 }
 
 pub trait MultiplyColour {
@@ -13,13 +14,17 @@ pub trait MultiplyColour {
 }
 
 impl WritePpm for Colour {
-    fn write_ppm(&self, file: &mut impl Write, samples: u32) {
-        let scale = 1.0 / samples as f64;
-
+    fn to_rgb8(&self, scale: f64) -> [u8; 3] {
         let r = ((self.data[0][0] * scale).sqrt() * 255.0) as u8;
         let g = ((self.data[0][1] * scale).sqrt() * 255.0) as u8;
         let b = ((self.data[0][2] * scale).sqrt() * 255.0) as u8;
 
+        [r, g, b]
+    }
+
+    fn write_ppm(&self, file: &mut impl Write, scale: f64) {
+        let [r, g, b] = self.to_rgb8(scale);
+
         file.write_all(format!("{r} {g} {b}\n").as_ref()).unwrap();
     }
 }
@@ -0,0 +1,170 @@
+// a piecewise-constant density over [0, 1) with one step per value
+pub struct Distribution1D {
+    values: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(values: Vec<f64>) -> Self {
+        let count = values.len() as f64;
+        let mut cdf = Vec::with_capacity(values.len() + 1);
+        cdf.push(0.0);
+        for value in &values {
+            cdf.push(cdf[cdf.len() - 1] + value.max(0.0) / count);
+        }
+
+        let integral = cdf[cdf.len() - 1];
+        if integral > 0.0 {
+            for entry in &mut cdf {
+                *entry /= integral;
+            }
+        } else {
+            // nothing to favour, so fall back to a uniform density
+            for (index, entry) in cdf.iter_mut().enumerate() {
+                *entry = index as f64 / count;
+            }
+        }
+
+        Self {
+            values,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // maps a uniform random number to a position in [0, 1) and the step it landed in
+    pub fn sample(&self, random: f64) -> (f64, usize) {
+        let index = self
+            .cdf
+            .partition_point(|&entry| entry <= random)
+            .clamp(1, self.values.len())
+            - 1;
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (random - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        (
+            ((index as f64 + offset.clamp(0.0, 1.0)) / self.values.len() as f64)
+                .min(1.0 - f64::EPSILON),
+            index,
+        )
+    }
+
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.values[index].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    pub fn index(&self, position: f64) -> usize {
+        ((position * self.values.len() as f64) as usize).min(self.values.len() - 1)
+    }
+}
+
+// a piecewise-constant density over the unit square, given as rows of values
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(values: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = values
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Self { rows, marginal }
+    }
+
+    // returns (column position, row position), each in [0, 1)
+    pub fn sample(&self, random_column: f64, random_row: f64) -> (f64, f64) {
+        let (row_position, row) = self.marginal.sample(random_row);
+        let (column_position, _) = self.rows[row].sample(random_column);
+        (column_position, row_position)
+    }
+
+    pub fn pdf(&self, column_position: f64, row_position: f64) -> f64 {
+        let row = self.marginal.index(row_position);
+        let column = self.rows[row].index(column_position);
+        self.marginal.pdf(row) * self.rows[row].pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const WIDTH: usize = 4;
+    const HEIGHT: usize = 3;
+    // the second column and the second row are empty
+    const VALUES: [f64; WIDTH * HEIGHT] =
+        [1.0, 0.0, 2.0, 1.0, 0.0, 0.0, 0.0, 0.0, 3.0, 0.0, 1.0, 4.0];
+
+    fn cell(column_position: f64, row_position: f64) -> usize {
+        let column = (column_position * WIDTH as f64) as usize;
+        let row = (row_position * HEIGHT as f64) as usize;
+        row * WIDTH + column
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let distribution = Distribution2D::new(&VALUES, WIDTH, HEIGHT);
+        let cell_area = 1.0 / (WIDTH * HEIGHT) as f64;
+        let mut integral = 0.0;
+        for row in 0..HEIGHT {
+            for column in 0..WIDTH {
+                let centre = (
+                    (column as f64 + 0.5) / WIDTH as f64,
+                    (row as f64 + 0.5) / HEIGHT as f64,
+                );
+                integral += distribution.pdf(centre.0, centre.1) * cell_area;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn samples_follow_the_pdf_and_skip_empty_cells() {
+        seed_thread_rng(5);
+        let distribution = Distribution2D::new(&VALUES, WIDTH, HEIGHT);
+        let samples = 200_000;
+        let mut histogram = [0usize; WIDTH * HEIGHT];
+        for _ in 0..samples {
+            let (column_position, row_position) = distribution.sample(gen_random(), gen_random());
+            assert!((0.0..1.0).contains(&column_position) && (0.0..1.0).contains(&row_position));
+            assert!(distribution.pdf(column_position, row_position) > 0.0);
+            histogram[cell(column_position, row_position)] += 1;
+        }
+
+        let total: f64 = VALUES.iter().sum();
+        for (index, &count) in histogram.iter().enumerate() {
+            let expected = VALUES[index] / total;
+            let actual = count as f64 / samples as f64;
+            assert!(
+                (actual - expected).abs() < 0.005,
+                "cell {index}: expected {expected}, got {actual}"
+            );
+        }
+
+        // the ends of the random range land next to, never inside, the empty cells
+        for random_column in [0.0, 0.5, 1.0 - f64::EPSILON] {
+            for random_row in [0.0, 0.5, 1.0 - f64::EPSILON] {
+                let (column_position, row_position) =
+                    distribution.sample(random_column, random_row);
+                assert!(VALUES[cell(column_position, row_position)] > 0.0);
+            }
+        }
+    }
+}
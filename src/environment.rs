@@ -0,0 +1,324 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::*;
+
+pub trait Environment: Send + Sync {
+    // radiance arriving along a ray that left the scene in `direction`
+    fn radiance(&self, direction: Vec3d) -> Colour;
+
+    // picks a direction towards the bright parts, for environments that can be used as lights
+    fn sample_direction(&self, _sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        None
+    }
+
+    // solid angle density of `sample_direction` producing `direction`
+    fn pdf_value(&self, _direction: Vec3d) -> f64 {
+        0.0
+    }
+}
+
+pub struct ConstantEnvironment {
+    colour: Colour,
+}
+
+impl ConstantEnvironment {
+    pub const fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn radiance(&self, _direction: Vec3d) -> Colour {
+        self.colour
+    }
+}
+
+pub struct GradientEnvironment {
+    horizon: Colour,
+    zenith: Colour,
+}
+
+impl GradientEnvironment {
+    pub const fn new(horizon: Colour, zenith: Colour) -> Self {
+        Self { horizon, zenith }
+    }
+}
+
+impl Default for GradientEnvironment {
+    fn default() -> Self {
+        Self::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn radiance(&self, direction: Vec3d) -> Colour {
+        let unit_direction = direction.normalise();
+        let t = 0.5 * (unit_direction.y + 1.0);
+        self.horizon * (1.0 - t) + self.zenith * t
+    }
+}
+
+// a latitude/longitude map, turned about the y axis by `rotation` radians
+pub struct EquirectangularEnvironment {
+    image: Arc<Image>,
+    rotation: f64,
+    intensity: f64,
+    // follows the luminance of the map, so bright spots like the sun are sampled often
+    distribution: Distribution2D,
+}
+
+impl EquirectangularEnvironment {
+    pub fn new(image: Arc<Image>, rotation: f64, intensity: f64) -> Self {
+        let mut luminance = Vec::with_capacity(image.width * image.height);
+        for y in 0..image.height {
+            // rows near the poles cover less of the sphere
+            let sin_theta = (PI * (y as f64 + 0.5) / image.height as f64).sin();
+            for x in 0..image.width {
+                let pixel = image.pixel(x, y);
+                luminance
+                    .push((0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&luminance, image.width, image.height);
+
+        Self {
+            image,
+            rotation,
+            intensity,
+            distribution,
+        }
+    }
+
+    // map coordinates of `direction`, with v running from the bottom of the image to the top
+    fn direction_to_uv(&self, direction: Vec3d) -> (f64, f64) {
+        let unit_direction = direction.normalise();
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = cos * unit_direction.x - sin * unit_direction.z;
+        let z = sin * unit_direction.x + cos * unit_direction.z;
+
+        let u = ((-z).atan2(x) + PI) / (2.0 * PI);
+        let v = (-unit_direction.y).clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+}
+
+impl Environment for EquirectangularEnvironment {
+    fn radiance(&self, direction: Vec3d) -> Colour {
+        let (u, v) = self.direction_to_uv(direction);
+        self.image.bilinear(u, v, WrapMode::Repeat, WrapMode::Clamp) * self.intensity
+    }
+
+    fn sample_direction(&self, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        let (random_u, random_row) = sampler.get_2d();
+        let (u, row) = self.distribution.sample(random_u, random_row);
+
+        // rows run from the top of the image, so `row` is the angle down from +y
+        let theta = PI * row;
+        let phi = 2.0 * PI * u - PI;
+        let x = theta.sin() * phi.cos();
+        let z = -theta.sin() * phi.sin();
+
+        let (sin, cos) = self.rotation.sin_cos();
+        Some(Vec3d::new(
+            cos * x + sin * z,
+            theta.cos(),
+            cos * z - sin * x,
+        ))
+    }
+
+    fn pdf_value(&self, direction: Vec3d) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // the map covers 2 pi by pi radians, stretched by sin theta on the sphere
+        self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// Preetham et al. 1999 daylight sky with a sun disk, in Y of kilocandela per square metre
+pub struct SkyEnvironment {
+    sun_direction: Vec3d,
+    sun_radiance: Colour,
+    cos_sun_radius: f64,
+    zenith: Vec3d,
+    perez: [[f64; 5]; 3],
+    // the distribution at the zenith, which the zenith values are normalised by
+    perez_zenith: Vec3d,
+    intensity: f64,
+}
+
+// angular radius of the sun seen from the earth
+const SUN_ANGULAR_RADIUS: f64 = 0.004_65;
+// luminance of the sun before the atmosphere dims it
+const SUN_LUMINANCE: f64 = 2.0e6;
+// brings kilocandela per square metre to roughly unit radiance for a midday sky
+const SKY_SCALE: f64 = 0.05;
+
+impl SkyEnvironment {
+    // `elevation` is above the horizon and `azimuth` turns from +z towards +x, both in radians
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64) -> Self {
+        let sun_direction = Vec3d::new(
+            azimuth.sin() * elevation.cos(),
+            elevation.sin(),
+            azimuth.cos() * elevation.cos(),
+        );
+
+        // the fit is only valid for a sun at or above the horizon
+        let theta_sun = PI / 2.0 - elevation.max(0.0);
+        let turbidity_squared = turbidity * turbidity;
+        let theta = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let cubic = |coefficients: [[f64; 4]; 3]| {
+            let row =
+                |weights: [f64; 4]| weights.iter().zip(theta).map(|(w, t)| w * t).sum::<f64>();
+            turbidity_squared * row(coefficients[0])
+                + turbidity * row(coefficients[1])
+                + row(coefficients[2])
+        };
+
+        let chi = (4.0 / 9.0 - turbidity / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith = Vec3d::new(
+            (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192,
+            cubic([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            cubic([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+        );
+
+        // A to E of the Perez distribution for Y, x and y
+        let perez = [
+            [
+                0.1787 * turbidity - 1.4630,
+                -0.3554 * turbidity + 0.4275,
+                -0.0227 * turbidity + 5.3251,
+                0.1206 * turbidity - 2.5771,
+                -0.0670 * turbidity + 0.3703,
+            ],
+            [
+                -0.0193 * turbidity - 0.2592,
+                -0.0665 * turbidity + 0.0008,
+                -0.0004 * turbidity + 0.2125,
+                -0.0641 * turbidity - 0.8989,
+                -0.0033 * turbidity + 0.0452,
+            ],
+            [
+                -0.0167 * turbidity - 0.2608,
+                -0.0950 * turbidity + 0.0092,
+                -0.0079 * turbidity + 0.2102,
+                -0.0441 * turbidity - 1.6537,
+                -0.0109 * turbidity + 0.0529,
+            ],
+        ];
+        let perez_zenith = Vec3d::new(
+            perez_distribution(perez[0], 1.0, theta_sun.cos()),
+            perez_distribution(perez[1], 1.0, theta_sun.cos()),
+            perez_distribution(perez[2], 1.0, theta_sun.cos()),
+        );
+
+        let sun_radiance = if elevation > 0.0 {
+            sun_transmittance(theta_sun, turbidity) * (SUN_LUMINANCE * SKY_SCALE)
+        } else {
+            Colour::new(0.0, 0.0, 0.0)
+        };
+
+        Self {
+            sun_direction,
+            sun_radiance,
+            cos_sun_radius: SUN_ANGULAR_RADIUS.cos(),
+            zenith,
+            perez,
+            perez_zenith,
+            intensity,
+        }
+    }
+
+    fn sky(&self, direction: Vec3d) -> Colour {
+        // directions below the horizon see the sky just above it
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+
+        let luminance = self.zenith.x * perez_distribution(self.perez[0], cos_theta, cos_gamma)
+            / self.perez_zenith.x;
+        let x = self.zenith.y * perez_distribution(self.perez[1], cos_theta, cos_gamma)
+            / self.perez_zenith.y;
+        let y = self.zenith.z * perez_distribution(self.perez[2], cos_theta, cos_gamma)
+            / self.perez_zenith.z;
+
+        xyy_to_rgb(x, y, luminance.max(0.0)) * SKY_SCALE
+    }
+}
+
+impl Environment for SkyEnvironment {
+    fn radiance(&self, direction: Vec3d) -> Colour {
+        let unit_direction = direction.normalise();
+        let mut radiance = self.sky(unit_direction);
+        if unit_direction.dot(self.sun_direction) >= self.cos_sun_radius {
+            radiance += self.sun_radiance;
+        }
+        radiance * self.intensity
+    }
+
+    // only the sun is sampled, the sky is smooth enough to be found by bsdf sampling
+    fn sample_direction(&self, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        if self.sun_radiance.max_component() <= 0.0 {
+            return None;
+        }
+
+        Some(local_to_world(
+            uniform_cone(sampler.get_2d(), self.cos_sun_radius),
+            self.sun_direction,
+        ))
+    }
+
+    fn pdf_value(&self, direction: Vec3d) -> f64 {
+        if self.sun_radiance.max_component() <= 0.0
+            || direction.normalise().dot(self.sun_direction) < self.cos_sun_radius
+        {
+            return 0.0;
+        }
+        uniform_cone_pdf(self.cos_sun_radius)
+    }
+}
+
+// relative brightness of the sky at `cos_theta` from the zenith and `cos_gamma` from the sun
+fn perez_distribution(coefficients: [f64; 5], cos_theta: f64, cos_gamma: f64) -> f64 {
+    let [a, b, c, d, e] = coefficients;
+    let gamma = cos_gamma.acos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Colour {
+    if y <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+    let big_x = x * luminance / y;
+    let big_z = (1.0 - x - y) * luminance / y;
+    Colour::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+    .max(Colour::new(0.0, 0.0, 0.0))
+}
+
+// Rayleigh and aerosol extinction along the path of sunlight, at red, green and blue wavelengths
+fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Colour {
+    let air_mass =
+        1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_sun.to_degrees()).max(0.0).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let channel = |wavelength: f64| {
+        let rayleigh = 0.008735 * wavelength.powf(-4.08);
+        let aerosol = beta * wavelength.powf(-1.3);
+        (-(rayleigh + aerosol) * air_mass).exp()
+    };
+    Colour::new(channel(0.65), channel(0.57), channel(0.475))
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+#[derive(Debug)]
+pub enum GridError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Format { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for GridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Format { .. } => None,
+        }
+    }
+}
+
+// densities at the centres of a regular lattice of voxels, x varying fastest, then y, then z
+pub struct DensityGrid {
+    pub size: [usize; 3],
+    pub values: Vec<f64>,
+    pub max_density: f64,
+}
+
+impl DensityGrid {
+    // reads a netpbm style file: `G3` followed by the size and values as text, or `G6` followed by
+    // the size and little-endian 32-bit floats
+    pub fn load(path: &Path) -> Result<Self, GridError> {
+        let bytes = std::fs::read(path).map_err(|source| GridError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let error = |message: &str| GridError::Format {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        };
+
+        match bytes.get(..2) {
+            Some(b"G3") | Some(b"G6") => parse_grid(&bytes).map_err(error),
+            _ => Err(error("unsupported grid format, expected G3 or G6")),
+        }
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [size_x, size_y, _] = self.size;
+        self.values[(z * size_y + y) * size_x + x]
+    }
+
+    // trilinear interpolation, with the unit cube `point` lies in spanning the whole grid
+    pub fn density(&self, point: Vec3d) -> f64 {
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let position = (point[axis] * self.size[axis] as f64 - 0.5)
+                .clamp(0.0, (self.size[axis] - 1) as f64);
+            lower[axis] = position.floor() as usize;
+            upper[axis] = (lower[axis] + 1).min(self.size[axis] - 1);
+            fraction[axis] = position - lower[axis] as f64;
+        }
+
+        let lerp = |a: f64, b: f64, t: f64| a * (1.0 - t) + b * t;
+        let row = |y: usize, z: usize| {
+            lerp(
+                self.value(lower[0], y, z),
+                self.value(upper[0], y, z),
+                fraction[0],
+            )
+        };
+        let slice = |z: usize| lerp(row(lower[1], z), row(upper[1], z), fraction[1]);
+        lerp(slice(lower[2]), slice(upper[2]), fraction[2])
+    }
+}
+
+fn parse_grid(bytes: &[u8]) -> Result<DensityGrid, &'static str> {
+    let (header, data_start) = parse_header(bytes, 4)?;
+    let mut size = [0; 3];
+    for axis in 0..3 {
+        size[axis] = match header[axis + 1].parse() {
+            Ok(value) if value > 0 => value,
+            _ => return Err("invalid grid dimensions"),
+        };
+    }
+    let count = checked_size(&size)?;
+
+    // a value count that does not match the size is a mistake in the file either way
+    let data = bytes.get(data_start..).unwrap_or_default();
+    let values: Vec<f64> = if header[0] == "G3" {
+        std::str::from_utf8(data)
+            .map_err(|_| "invalid grid data")?
+            .split_ascii_whitespace()
+            .map(|token| token.parse().map_err(|_| "invalid density value"))
+            .collect::<Result<_, _>>()?
+    } else {
+        if data.len() % 4 != 0 {
+            return Err("grid data is not a whole number of values");
+        }
+        data.chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64)
+            .collect()
+    };
+
+    if values.len() < count {
+        return Err("not enough grid data");
+    }
+    if values.len() > count {
+        return Err("more grid data than the size holds");
+    }
+    if values
+        .iter()
+        .any(|value| !value.is_finite() || *value < 0.0)
+    {
+        return Err("densities must be finite and not negative");
+    }
+
+    let max_density = values.iter().copied().fold(0.0, f64::max);
+    Ok(DensityGrid {
+        size,
+        values,
+        max_density,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2x1 grid rising by one along x and by two along y
+    const SMALL: &[u8] = b"G3\n# x fastest\n2 2 1\n0 1\n2 3\n";
+
+    fn binary(size: &str, values: &[f32]) -> Vec<u8> {
+        let mut bytes = format!("G6\n{size}\n").into_bytes();
+        bytes.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn parses_text_and_binary_grids() {
+        for bytes in [SMALL.to_vec(), binary("2 2 1", &[0.0, 1.0, 2.0, 3.0])] {
+            let grid = parse_grid(&bytes).unwrap();
+            assert_eq!(grid.size, [2, 2, 1]);
+            assert_eq!(grid.values, [0.0, 1.0, 2.0, 3.0]);
+            assert_eq!(grid.max_density, 3.0);
+        }
+    }
+
+    #[test]
+    fn interpolates_between_cell_centres() {
+        let grid = parse_grid(SMALL).unwrap();
+        let density = |x: f64, y: f64| grid.density(Vec3d::new(x, y, 0.5));
+
+        // cell centres hold their own values
+        assert_eq!(density(0.25, 0.25), 0.0);
+        assert_eq!(density(0.75, 0.25), 1.0);
+        assert_eq!(density(0.25, 0.75), 2.0);
+        assert_eq!(density(0.75, 0.75), 3.0);
+
+        // halfway between centres, and the middle of all four
+        assert_eq!(density(0.5, 0.25), 0.5);
+        assert_eq!(density(0.25, 0.5), 1.0);
+        assert_eq!(density(0.5, 0.5), 1.5);
+
+        // from the outermost centres to the edges the value is held, not extrapolated
+        assert_eq!(density(0.0, 0.0), 0.0);
+        assert_eq!(density(1.0, 1.0), 3.0);
+        assert_eq!(density(1.0, 0.5), 2.0);
+    }
+
+    #[test]
+    fn rejects_wrong_value_counts() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"G3\n2 2 1\n0 1 2\n", "not enough grid data"),
+            (
+                b"G3\n2 2 1\n0 1 2 3 4\n",
+                "more grid data than the size holds",
+            ),
+            (&binary("2 2 1", &[0.0; 3]), "not enough grid data"),
+            (
+                &binary("2 2 1", &[0.0; 5]),
+                "more grid data than the size holds",
+            ),
+            (b"G3\n2 0 1\n", "invalid grid dimensions"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(parse_grid(bytes).err(), Some(expected));
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Format { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Format { .. } => None,
+        }
+    }
+}
+
+// linear RGB pixels, top row first
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Colour>,
+}
+
+impl Image {
+    pub fn load(path: &Path) -> Result<Self, ImageError> {
+        let bytes = std::fs::read(path).map_err(|source| ImageError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let error = |message: &str| ImageError::Format {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        };
+
+        match bytes.get(..2) {
+            Some(b"P3") | Some(b"P6") => parse_ppm(&bytes).map_err(error),
+            Some(b"PF") => parse_pfm(&bytes).map_err(error),
+            Some(b"#?") => parse_radiance_hdr(&bytes).map_err(error),
+            _ => Err(error(
+                "unsupported image format, expected PPM, PFM or Radiance HDR",
+            )),
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Colour {
+        self.pixels[y * self.width + x]
+    }
+
+    // bilinear filtering between the four texel centres around (u, v), with v = 0 at the bottom
+    pub fn bilinear(&self, u: f64, v: f64, wrap_x: WrapMode, wrap_y: WrapMode) -> Colour {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        if !x.is_finite() || !y.is_finite() {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let texel = |dx: isize, dy: isize| {
+            self.pixel(
+                wrap_x.apply(x0 as isize + dx, self.width),
+                wrap_y.apply(y0 as isize + dy, self.height),
+            )
+        };
+
+        let top = texel(0, 0) * (1.0 - fx) + texel(1, 0) * fx;
+        let bottom = texel(0, 1) * (1.0 - fx) + texel(1, 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+// splits a netpbm style header into `count` tokens and returns them with the offset of the data
+pub fn parse_header(bytes: &[u8], count: usize) -> Result<(Vec<&str>, usize), &'static str> {
+    let mut tokens = vec![];
+    let mut position = 0;
+
+    while tokens.len() < count {
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        if bytes.get(position) == Some(&b'#') {
+            while position < bytes.len() && bytes[position] != b'\n' {
+                position += 1;
+            }
+            continue;
+        }
+        let start = position;
+        while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        if start == position {
+            return Err("truncated header");
+        }
+        tokens.push(std::str::from_utf8(&bytes[start..position]).map_err(|_| "invalid header")?);
+    }
+
+    // exactly one whitespace byte separates the header from binary data
+    Ok((tokens, position + 1))
+}
+
+fn parse_dimension(token: &str) -> Result<usize, &'static str> {
+    match token.parse() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err("invalid image dimensions"),
+    }
+}
+
+// multiplies out an image size, failing rather than wrapping on absurd headers
+pub fn checked_size(factors: &[usize]) -> Result<usize, &'static str> {
+    factors
+        .iter()
+        .try_fold(1usize, |size, &factor| size.checked_mul(factor))
+        .ok_or("image dimensions too large")
+}
+
+fn parse_ppm(bytes: &[u8]) -> Result<Image, &'static str> {
+    let (header, data_start) = parse_header(bytes, 4)?;
+    let width = parse_dimension(header[1])?;
+    let height = parse_dimension(header[2])?;
+    let max_value: u32 = match header[3].parse() {
+        Ok(value) if (1..=255).contains(&value) => value,
+        _ => return Err("only 8-bit PPM images are supported"),
+    };
+
+    // every sample takes at least one byte, in either encoding
+    let sample_count = checked_size(&[width, height, 3])?;
+    let data = bytes.get(data_start..).unwrap_or_default();
+    if data.len() < sample_count {
+        return Err("not enough pixel data");
+    }
+
+    let samples: Vec<u32> = if header[0] == "P3" {
+        std::str::from_utf8(data)
+            .map_err(|_| "invalid pixel data")?
+            .split_ascii_whitespace()
+            .map(|token| token.parse().map_err(|_| "invalid pixel value"))
+            .collect::<Result<_, _>>()?
+    } else {
+        data.iter().map(|&byte| byte as u32).collect()
+    };
+
+    if samples.len() < sample_count {
+        return Err("not enough pixel data");
+    }
+
+    // undo the gamma 2 curve the renderer writes images with
+    let decode = |value: u32| (value.min(max_value) as f64 / max_value as f64).powi(2);
+    let pixels = samples
+        .chunks_exact(3)
+        .take(sample_count / 3)
+        .map(|rgb| Colour::new(decode(rgb[0]), decode(rgb[1]), decode(rgb[2])))
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn parse_pfm(bytes: &[u8]) -> Result<Image, &'static str> {
+    let (header, data_start) = parse_header(bytes, 4)?;
+    if header[0] != "PF" {
+        return Err("only colour PFM images are supported");
+    }
+    let width = parse_dimension(header[1])?;
+    let height = parse_dimension(header[2])?;
+    let scale: f64 = header[3].parse().map_err(|_| "invalid PFM scale")?;
+    let little_endian = scale < 0.0;
+
+    let value_count = checked_size(&[width, height, 3])?;
+    let data = bytes.get(data_start..).unwrap_or_default();
+    if data.len() < checked_size(&[value_count, 4])? {
+        return Err("not enough pixel data");
+    }
+
+    let values: Vec<f64> = data
+        .chunks_exact(4)
+        .take(value_count)
+        .map(|chunk| {
+            let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+            if little_endian {
+                f32::from_le_bytes(chunk) as f64
+            } else {
+                f32::from_be_bytes(chunk) as f64
+            }
+        })
+        .collect();
+
+    // PFM rows are stored bottom first
+    let mut pixels = Vec::with_capacity(value_count / 3);
+    for row in values.chunks_exact(width * 3).rev() {
+        for rgb in row.chunks_exact(3) {
+            pixels.push(Colour::new(rgb[0], rgb[1], rgb[2]));
+        }
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn parse_radiance_hdr(bytes: &[u8]) -> Result<Image, &'static str> {
+    let mut position = 0;
+    let mut next_line = || {
+        let start = position;
+        while position < bytes.len() && bytes[position] != b'\n' {
+            position += 1;
+        }
+        position += 1;
+        std::str::from_utf8(bytes.get(start..position - 1).unwrap_or_default())
+            .map_err(|_| "invalid header")
+    };
+
+    loop {
+        let line = next_line()?;
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err("only RGBE Radiance HDR images are supported");
+        }
+        if line.is_empty() {
+            break;
+        }
+    }
+
+    let resolution: Vec<&str> = next_line()?.split_whitespace().collect();
+    if resolution.len() != 4 || resolution[0] != "-Y" || resolution[2] != "+X" {
+        return Err("only `-Y height +X width` Radiance HDR images are supported");
+    }
+    let height = parse_dimension(resolution[1])?;
+    let width = parse_dimension(resolution[3])?;
+
+    // the smallest a scanline can be, as flat pixels or at best 127 pixel runs per component
+    let new_rle_width = (8..0x8000).contains(&width);
+    let min_scanline = if new_rle_width {
+        4 + 8 * width.div_ceil(127)
+    } else {
+        checked_size(&[width, 4])?
+    };
+    let mut data = bytes.get(position..).unwrap_or_default();
+    if data.len() < checked_size(&[min_scanline, height])? {
+        return Err("truncated pixel data");
+    }
+
+    let mut pixels = Vec::with_capacity(checked_size(&[width, height])?);
+    let mut scanline = vec![[0u8; 4]; width];
+
+    for _ in 0..height {
+        let new_rle =
+            new_rle_width && data.len() >= 4 && data[0] == 2 && data[1] == 2 && data[2] & 0x80 == 0;
+
+        if new_rle {
+            if ((data[2] as usize) << 8 | data[3] as usize) != width {
+                return Err("scanline width mismatch");
+            }
+            data = &data[4..];
+
+            // each of the four components is run length encoded separately
+            for component in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let (&count, rest) = data.split_first().ok_or("truncated pixel data")?;
+                    if count > 128 {
+                        let run = (count - 128) as usize;
+                        let (&value, rest) = rest.split_first().ok_or("truncated pixel data")?;
+                        if x + run > width {
+                            return Err("run overflows scanline");
+                        }
+                        scanline[x..x + run]
+                            .iter_mut()
+                            .for_each(|pixel| pixel[component] = value);
+                        x += run;
+                        data = rest;
+                    } else {
+                        let run = count as usize;
+                        if run == 0 || x + run > width || rest.len() < run {
+                            return Err("invalid run length");
+                        }
+                        for (pixel, &value) in scanline[x..x + run].iter_mut().zip(rest) {
+                            pixel[component] = value;
+                        }
+                        x += run;
+                        data = &rest[run..];
+                    }
+                }
+            }
+        } else {
+            if data.len() < width * 4 {
+                return Err("truncated pixel data");
+            }
+            for (pixel, rgbe) in scanline.iter_mut().zip(data.chunks_exact(4)) {
+                pixel.copy_from_slice(rgbe);
+            }
+            data = &data[width * 4..];
+        }
+
+        pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+            if e == 0 {
+                Colour::new(0.0, 0.0, 0.0)
+            } else {
+                let scale = 2.0f64.powi(e as i32 - 136);
+                Colour::new(r as f64 * scale, g as f64 * scale, b as f64 * scale)
+            }
+        }));
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pfm(header: &str, values: &[f32]) -> Vec<u8> {
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn parses_small_images() {
+        let image = parse_ppm(b"P3\n2 1\n255\n255 0 0  0 255 0\n").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixel(0, 0).x(), 1.0);
+        assert_eq!(image.pixel(1, 0).y(), 1.0);
+
+        // bottom row first on disk
+        let image = parse_pfm(&pfm("PF\n1 2\n-1.0\n", &[1.0, 1.0, 1.0, 2.0, 2.0, 2.0])).unwrap();
+        assert_eq!(image.pixel(0, 0).x(), 2.0);
+        assert_eq!(image.pixel(0, 1).x(), 1.0);
+
+        let image =
+            parse_radiance_hdr(b"#?RADIANCE\n\n-Y 1 +X 2\n\x80\x80\x80\x81\0\0\0\0").unwrap();
+        assert_eq!(image.pixel(0, 0).x(), 1.0);
+        assert_eq!(image.pixel(1, 0).x(), 0.0);
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        let cases: [(&str, Result<Image, &str>); 7] = [
+            ("ppm header", parse_ppm(b"P6\n2 2")),
+            ("ppm binary", parse_ppm(b"P6\n2 2\n255\n\0\0\0")),
+            ("ppm ascii", parse_ppm(b"P3\n2 1\n255\n1 2 3 4 5\n")),
+            ("pfm header", parse_pfm(b"PF\n2\n")),
+            ("pfm data", parse_pfm(&pfm("PF\n1 1\n-1.0\n", &[1.0, 1.0]))),
+            ("hdr header", parse_radiance_hdr(b"#?RADIANCE\n\n-Y 2\n")),
+            (
+                "hdr data",
+                parse_radiance_hdr(b"#?RADIANCE\n\n-Y 2 +X 1\n\x80\x80\x80\x81"),
+            ),
+        ];
+        for (case, result) in cases {
+            assert!(result.is_err(), "{case} was accepted");
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_headers_without_allocating() {
+        let huge = usize::MAX / 2;
+        let overflowing = [
+            parse_ppm(format!("P6\n{huge} {huge}\n255\n").as_bytes()),
+            parse_pfm(&pfm(&format!("PF\n{huge} 3\n-1.0\n"), &[])),
+            parse_radiance_hdr(format!("#?RADIANCE\n\n-Y {huge} +X {huge}\n").as_bytes()),
+        ];
+        for result in overflowing {
+            assert_eq!(result.err(), Some("image dimensions too large"));
+        }
+
+        // sizes that fit in memory arithmetic but not in the file
+        let short = [
+            parse_ppm(b"P6\n100000 100000\n255\n\0\0\0"),
+            parse_pfm(&pfm("PF\n100000 100000\n-1.0\n", &[0.0; 3])),
+        ];
+        for result in short {
+            assert_eq!(result.err(), Some("not enough pixel data"));
+        }
+        assert_eq!(
+            parse_radiance_hdr(b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02").err(),
+            Some("truncated pixel data")
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use crate::*;
+
+// the chance of a path surviving roulette never exceeds this, so bright paths still terminate
+const MAX_SURVIVAL_PROBABILITY: f64 = 0.95;
+
+pub struct PathTracer {
+    max_depth: u32,
+    russian_roulette_depth: u32,
+}
+
+impl PathTracer {
+    pub const fn new(max_depth: u32, russian_roulette_depth: u32) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth,
+        }
+    }
+
+    // follows the path one bounce at a time, carrying the product of attenuations as throughput
+    pub fn radiance<T: Hittable>(
+        &self,
+        ray: Ray,
+        world: &T,
+        lights: &Lights,
+        environment: &dyn Environment,
+        sampler: &mut dyn Sampler,
+    ) -> Colour {
+        let mut radiance = Colour::new(0.0, 0.0, 0.0);
+        let mut throughput = Colour::new(1.0, 1.0, 1.0);
+        let mut ray = ray;
+        // density of the bsdf sample that produced `ray`, none for camera rays and specular bounces
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for depth in 0.. {
+            let Some(hit_record) = world.hit_sampled(&ray, 1.0e-6, f64::INFINITY, sampler) else {
+                let weight = match bsdf_pdf {
+                    Some(bsdf_pdf) => {
+                        power_heuristic(bsdf_pdf, environment.pdf_value(ray.direction))
+                    }
+                    None => 1.0,
+                };
+                radiance +=
+                    throughput.multiply_colour(environment.radiance(ray.direction)) * weight;
+                break;
+            };
+
+            let emitted = hit_record.material.emitted(&hit_record);
+            if emitted.max_component() > 0.0 {
+                // light sampling could have found this emitter too, so both strategies share it
+                let weight = match bsdf_pdf {
+                    Some(bsdf_pdf) => {
+                        let light_pdf =
+                            lights.shapes.pdf_value(ray.origin, ray.direction, ray.time);
+                        power_heuristic(bsdf_pdf, light_pdf)
+                    }
+                    None => 1.0,
+                };
+                radiance += throughput.multiply_colour(emitted) * weight;
+            }
+
+            if depth >= self.max_depth {
+                break;
+            }
+
+            // direct light is gathered whether or not the bsdf sample below survives, since a
+            // failed sample only ends the indirect path
+            if !hit_record.material.is_specular() {
+                if !lights.shapes.is_empty() {
+                    let direct =
+                        Self::sample_shape_light(world, &lights.shapes, &ray, &hit_record, sampler);
+                    radiance += throughput.multiply_colour(direct);
+                }
+                let direct =
+                    Self::sample_environment(world, environment, &ray, &hit_record, sampler);
+                radiance += throughput.multiply_colour(direct);
+                for light in &lights.punctual {
+                    let direct = Self::sample_punctual_light(
+                        world,
+                        light.as_ref(),
+                        &ray,
+                        &hit_record,
+                        sampler,
+                    );
+                    radiance += throughput.multiply_colour(direct);
+                }
+            }
+
+            let Some(scatter_record) = hit_record.material.sample(&ray, &hit_record, sampler)
+            else {
+                break;
+            };
+            bsdf_pdf = (!scatter_record.is_specular).then_some(scatter_record.pdf);
+
+            throughput = throughput.multiply_colour(scatter_record.attenuation);
+
+            // unbiased termination: surviving paths are boosted by the inverse survival chance
+            if depth >= self.russian_roulette_depth {
+                let survival_probability = throughput.max_component().min(MAX_SURVIVAL_PROBABILITY);
+                if survival_probability <= 0.0 || sampler.get_1d() >= survival_probability {
+                    break;
+                }
+                throughput /= survival_probability;
+            }
+
+            ray = scatter_record.scattered;
+        }
+
+        radiance
+    }
+
+    // next-event estimation: connects a hit to a point on an emissive shape with a shadow ray
+    fn sample_shape_light<T: Hittable>(
+        world: &T,
+        lights: &HittableList,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Colour {
+        let black = Colour::new(0.0, 0.0, 0.0);
+
+        let Some(light_point) = lights.sample_point(hit_record.point, ray.time, sampler) else {
+            return black;
+        };
+        let direction = light_point - hit_record.point;
+        let light_pdf = lights.pdf_value(hit_record.point, direction, ray.time);
+        if light_pdf <= 0.0 {
+            return black;
+        }
+
+        let outgoing = -ray.direction.normalise();
+        let incoming = direction.normalise();
+        let bsdf = hit_record.material.eval(hit_record, outgoing, incoming);
+        if bsdf.max_component() <= 0.0 {
+            return black;
+        }
+
+        // the light is only visible if nothing is hit before the sampled point
+        let shadow_ray = Ray::new(hit_record.point, direction, ray.time);
+        match world.hit_sampled(&shadow_ray, 1.0e-6, f64::INFINITY, sampler) {
+            Some(light_hit) if light_hit.t > 1.0 - 1.0e-4 => {
+                let emitted = light_hit.material.emitted(&light_hit);
+                let bsdf_pdf = hit_record.material.pdf(hit_record, outgoing, incoming);
+                let weight = power_heuristic(light_pdf, bsdf_pdf);
+                bsdf.multiply_colour(emitted) * (weight / light_pdf)
+            }
+            _ => black,
+        }
+    }
+
+    // shadow ray towards a direction picked by the environment, which only counts if it escapes
+    fn sample_environment<T: Hittable>(
+        world: &T,
+        environment: &dyn Environment,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Colour {
+        let black = Colour::new(0.0, 0.0, 0.0);
+
+        let Some(direction) = environment.sample_direction(sampler) else {
+            return black;
+        };
+        let environment_pdf = environment.pdf_value(direction);
+        if environment_pdf <= 0.0 {
+            return black;
+        }
+
+        let outgoing = -ray.direction.normalise();
+        let bsdf = hit_record.material.eval(hit_record, outgoing, direction);
+        if bsdf.max_component() <= 0.0 {
+            return black;
+        }
+
+        let shadow_ray = Ray::new(hit_record.point, direction, ray.time);
+        if world
+            .hit_sampled(&shadow_ray, 1.0e-6, f64::INFINITY, sampler)
+            .is_some()
+        {
+            return black;
+        }
+
+        let bsdf_pdf = hit_record.material.pdf(hit_record, outgoing, direction);
+        let weight = power_heuristic(environment_pdf, bsdf_pdf);
+        bsdf.multiply_colour(environment.radiance(direction)) * (weight / environment_pdf)
+    }
+
+    // punctual lights are a single direction, so there is nothing to weigh them against
+    fn sample_punctual_light<T: Hittable>(
+        world: &T,
+        light: &dyn Light,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Colour {
+        let black = Colour::new(0.0, 0.0, 0.0);
+
+        let sample = light.sample(hit_record.point);
+        if sample.radiance.max_component() <= 0.0 {
+            return black;
+        }
+
+        let outgoing = -ray.direction.normalise();
+        let bsdf = hit_record
+            .material
+            .eval(hit_record, outgoing, sample.direction);
+        if bsdf.max_component() <= 0.0 {
+            return black;
+        }
+
+        let shadow_ray = Ray::new(hit_record.point, sample.direction, ray.time);
+        let t_max = sample.distance * (1.0 - 1.0e-4);
+        if world
+            .hit_sampled(&shadow_ray, 1.0e-6, t_max, sampler)
+            .is_some()
+        {
+            return black;
+        }
+
+        bsdf.multiply_colour(sample.radiance)
+    }
+}
+
+// Veach's power heuristic with an exponent of two, weighting the strategy that produced `pdf`
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let total = pdf_squared + other_pdf * other_pdf;
+    if total > 0.0 {
+        pdf_squared / total
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    // mean radiance of rays spread over a rough metal sphere lit only by `environment`
+    fn furnace(environment: &dyn Environment) -> f64 {
+        seed_thread_rng(11);
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3d::ZERO,
+            1.0,
+            Box::new(Metal::new(Colour::fill(1.0), 0.5)),
+        )));
+        let lights = Lights::new(HittableList::new(), vec![]);
+        let integrator = PathTracer::new(8, 8);
+        let mut sampler = IndependentSampler;
+
+        let count = 20000;
+        let mut total = 0.0;
+        for _ in 0..count {
+            let target = Vec3d::new(
+                gen_random_range(-0.9, 0.9),
+                gen_random_range(-0.9, 0.9),
+                0.0,
+            );
+            let origin = Vec3d::new(0.0, 0.0, 5.0);
+            let ray = Ray::new(origin, target - origin, 0.0);
+            total += integrator
+                .radiance(ray, &world, &lights, environment, &mut sampler)
+                .x;
+        }
+        total / count as f64
+    }
+
+    #[test]
+    fn environment_sampling_matches_a_constant_environment() {
+        let image = Image {
+            width: 8,
+            height: 4,
+            pixels: vec![Colour::fill(1.0); 32],
+        };
+        let constant = furnace(&ConstantEnvironment::new(Colour::fill(1.0)));
+        let sampled = furnace(&EquirectangularEnvironment::new(Arc::new(image), 0.0, 1.0));
+        assert!(
+            (constant - sampled).abs() < 0.01,
+            "constant {constant}, sampled {sampled}"
+        );
+    }
+}
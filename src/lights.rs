@@ -0,0 +1,197 @@
+use crate::*;
+
+pub struct LightSample {
+    // unit direction from the shaded point towards the light
+    pub direction: Vec3d,
+    // distance to the light, infinite for lights that are infinitely far away
+    pub distance: f64,
+    // radiance arriving at the shaded point if nothing is in the way
+    pub radiance: Colour,
+}
+
+// lights without geometry; rays can never hit them, so they are only found by shadow rays
+pub trait Light: Send + Sync {
+    fn sample(&self, point: Vec3d) -> LightSample;
+}
+
+pub struct PointLight {
+    position: Vec3d,
+    intensity: Colour,
+}
+
+impl PointLight {
+    pub const fn new(position: Vec3d, intensity: Colour) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Vec3d) -> LightSample {
+        let to_light = self.position - point;
+        let distance_squared = to_light.length_squared();
+        LightSample {
+            direction: to_light.normalise(),
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity / distance_squared,
+        }
+    }
+}
+
+// a point light limited to a cone, fading out between the inner and outer angles
+pub struct SpotLight {
+    position: Vec3d,
+    direction: Vec3d,
+    intensity: Colour,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3d,
+        direction: Vec3d,
+        intensity: Colour,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        Self {
+            position,
+            direction: direction.normalise(),
+            intensity,
+            cos_inner: inner_angle.min(outer_angle).cos(),
+            cos_outer: outer_angle.cos(),
+        }
+    }
+
+    fn falloff(&self, cosine: f64) -> f64 {
+        if cosine >= self.cos_inner {
+            return 1.0;
+        }
+        if cosine <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cosine - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Vec3d) -> LightSample {
+        let to_light = self.position - point;
+        let distance_squared = to_light.length_squared();
+        let direction = to_light.normalise();
+        let falloff = self.falloff((-direction).dot(self.direction));
+        LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity * (falloff / distance_squared),
+        }
+    }
+}
+
+// parallel light such as the sun, shining along `direction`
+pub struct DirectionalLight {
+    direction: Vec3d,
+    irradiance: Colour,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3d, irradiance: Colour) -> Self {
+        Self {
+            direction: direction.normalise(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: Vec3d) -> LightSample {
+        LightSample {
+            direction: -self.direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        }
+    }
+}
+
+// everything the integrator can aim shadow rays at
+pub struct Lights {
+    // emissive shapes that are also part of the world
+    pub shapes: HittableList,
+    pub punctual: Vec<Box<dyn Light>>,
+}
+
+impl Lights {
+    pub const fn new(shapes: HittableList, punctual: Vec<Box<dyn Light>>) -> Self {
+        Self { shapes, punctual }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Vec3d, expected: Vec3d) {
+        assert!(
+            (actual - expected).length() < 1.0e-9,
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    #[test]
+    fn point_light_falls_off_with_the_square_of_distance() {
+        let light = PointLight::new(Vec3d::new(3.0, 4.0, 0.0), Colour::new(50.0, 25.0, 0.0));
+        let sample = light.sample(Vec3d::ZERO);
+        assert_close(sample.direction, Vec3d::new(0.6, 0.8, 0.0));
+        assert!((sample.distance - 5.0).abs() < 1.0e-9);
+        assert_close(sample.radiance, Colour::new(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn spot_light_fades_between_its_cone_angles() {
+        let (inner, outer) = (30.0f64.to_radians(), 45.0f64.to_radians());
+        let light = SpotLight::new(
+            Vec3d::new(0.0, 2.0, 0.0),
+            Vec3d::new(0.0, -3.0, 0.0),
+            Colour::fill(8.0),
+            inner,
+            outer,
+        );
+
+        // on the axis, at full strength
+        let sample = light.sample(Vec3d::ZERO);
+        assert_close(sample.direction, Vec3d::Y);
+        assert_close(sample.radiance, Colour::fill(2.0));
+
+        // halfway between the cosines of the two angles the smoothstep is one half
+        let angle = ((inner.cos() + outer.cos()) / 2.0).acos();
+        let offset = 2.0 * angle.tan();
+        let sample = light.sample(Vec3d::new(offset, 0.0, 0.0));
+        assert_close(
+            sample.radiance,
+            Colour::fill(0.5 * 8.0 / (4.0 + offset * offset)),
+        );
+
+        // outside the outer cone, and behind the light
+        let sample = light.sample(Vec3d::new(2.0 * 60.0f64.to_radians().tan(), 0.0, 0.0));
+        assert_close(sample.radiance, Colour::fill(0.0));
+        assert_close(
+            light.sample(Vec3d::new(0.0, 4.0, 0.0)).radiance,
+            Colour::fill(0.0),
+        );
+    }
+
+    #[test]
+    fn directional_light_arrives_from_the_opposite_direction() {
+        let light = DirectionalLight::new(Vec3d::new(0.0, -2.0, 0.0), Colour::new(1.0, 2.0, 3.0));
+        for point in [Vec3d::ZERO, Vec3d::new(100.0, -50.0, 7.0)] {
+            let sample = light.sample(point);
+            assert_close(sample.direction, Vec3d::Y);
+            assert_eq!(sample.distance, f64::INFINITY);
+            assert_close(sample.radiance, Colour::new(1.0, 2.0, 3.0));
+        }
+    }
+}
@@ -1,144 +1,136 @@
-use std::io::Write;
+use std::path::PathBuf;
 
-use rand::Rng;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use spyder_math::*;
 
+use aabb::*;
+use bvh::*;
 use camera::*;
+use cli::*;
 use colour::*;
+use distribution::*;
+use environment::*;
+use grid::*;
+use image::*;
+use integrator::*;
+use lights::*;
 use materials::*;
+use medium::*;
+use motion::*;
+use obj::*;
+use output::*;
 use ray::*;
+use render::*;
+use sampler::*;
+use sampling::*;
+use scene::*;
+use scenes::*;
+use textures::*;
 use utils::*;
 
 use crate::primitives::*;
 
+mod aabb;
+mod bvh;
 mod camera;
+mod cli;
 mod colour;
+mod distribution;
+mod environment;
+mod grid;
+mod image;
+mod integrator;
+mod lights;
 mod materials;
+mod medium;
+mod motion;
+mod obj;
+mod output;
 mod primitives;
 mod ray;
+mod render;
+mod sampler;
+mod sampling;
+mod scene;
+mod scenes;
+mod textures;
 mod utils;
 
 const ASPECT_RATIO: f64 = 3.0 / 2.0;
 const IMAGE_WIDTH: u32 = 1200;
-const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
 const SAMPLES_PER_PIXEL: u32 = 1;
 const SAMPLE_RANGE: f64 = 0.5;
-const MAX_DEPTH: u32 = 2;
+const MAX_DEPTH: u32 = 50;
+const RUSSIAN_ROULETTE_DEPTH: u32 = 3;
+const TILE_SIZE: u32 = 32;
+// 16384x16384, which keeps the framebuffer within a few gigabytes
+const MAX_IMAGE_PIXELS: usize = 1 << 28;
+const SEED: u64 = 0;
 
 fn main() -> std::io::Result<()> {
-    let mut rng = rand::thread_rng();
-
-    let world = random_world();
-
-    let look_from = Vec3d::new(13.0, 2.0, 3.0);
-    let look_at = Vec3d::new(0.0, 0.0, 0.0);
-    let up_vector = Vec3d::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let camera = Camera::new(look_from, look_at, up_vector, 20.0, aperture, dist_to_focus);
-
-    let mut file = std::fs::File::create("image.ppm")?;
-    file.write_all(format!("P3\n{IMAGE_WIDTH} {IMAGE_HEIGHT}\n255\n").as_ref())?;
-
-    for y in 0..IMAGE_HEIGHT {
-        for x in 0..IMAGE_WIDTH {
-            if ((y * IMAGE_WIDTH + x) % (IMAGE_HEIGHT * IMAGE_WIDTH / 100)) == 0 {
-                println!(
-                    "{}%",
-                    ((y * IMAGE_WIDTH + x) * 100) / (IMAGE_HEIGHT * IMAGE_WIDTH)
-                );
-            }
-
-            // flip image
-            let x = x as f64;
-            let y = (IMAGE_HEIGHT - y) as f64;
-
-            let mut pixel_colour: Colour = Vec3d::new(0.0, 0.0, 0.0);
-
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u =
-                    (x + rng.gen_range(-SAMPLE_RANGE..=SAMPLE_RANGE)) / (IMAGE_WIDTH - 1) as f64;
-                let v =
-                    (y + rng.gen_range(-SAMPLE_RANGE..=SAMPLE_RANGE)) / (IMAGE_HEIGHT - 1) as f64;
-                let ray = camera.get_ray(u, v);
-                pixel_colour += ray.ray_colour(&world, MAX_DEPTH);
-            }
-            pixel_colour.write_ppm(&mut file, SAMPLES_PER_PIXEL);
+    let cli = Cli::parse();
+
+    let format = cli.output_format().unwrap_or_else(|message| {
+        Cli::command()
+            .error(ErrorKind::ValueValidation, message)
+            .exit()
+    });
+
+    let mut scene = match load_scene(cli.scene.as_deref(), cli.seed.unwrap_or(SEED)) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
         }
+    };
+
+    if let Err(message) = cli.apply(&mut scene.settings) {
+        Cli::command()
+            .error(ErrorKind::ValueValidation, message)
+            .exit();
     }
 
-    Ok(())
-}
+    if cli.frames.is_some() && scene.camera.animation.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "`--frames` needs a scene with camera keyframes",
+            )
+            .exit();
+    }
 
-fn random_world() -> HittableList {
-    let mut world = HittableList::new();
-
-    let ground_material = Box::new(Lambertian::new(Vec3d::new(0.5, 0.5, 0.5)));
-    world.add(Box::new(Sphere::new(
-        Vec3d::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
-
-    for i in -10..=10 {
-        for j in -10..=10 {
-            let center = Vec3d::new(
-                i as f64 + 0.9 * gen_random(),
-                0.2,
-                j as f64 + 0.9 * gen_random(),
-            );
-
-            if (center - Vec3d::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let choose_mat = gen_random();
-                let sphere_material: Box<dyn Material>;
-
-                if choose_mat < 0.8 {
-                    let albedo = Vec3d::new(
-                        gen_random() * gen_random(),
-                        gen_random() * gen_random(),
-                        gen_random() * gen_random(),
-                    );
-                    sphere_material = Box::new(Lambertian::new(albedo));
-                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
-                } else if choose_mat < 0.95 {
-                    let albedo = Vec3d::new(gen_random(), gen_random(), gen_random());
-                    let fuzz = gen_random_range(0.0, 0.5);
-                    sphere_material = Box::new(Metal::new(albedo, fuzz));
-                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
-                } else {
-                    sphere_material = Box::new(Dielectric::new(1.5));
-                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
-                }
-            }
+    let settings = scene.settings;
+    let lights = Lights::new(scene.world.emissive_objects(), scene.lights);
+    let world = BvhNode::new(scene.world);
+
+    // a single image is taken at frame 0, a range writes one numbered image per frame
+    let frames: Vec<(u32, PathBuf)> = match &cli.frames {
+        Some(frames) => frames
+            .clone()
+            .map(|frame| (frame, numbered_path(&cli.output, frame)))
+            .collect(),
+        None => vec![(0, cli.output.clone())],
+    };
+
+    for (frame, path) in frames {
+        if cli.frames.is_some() {
+            println!("frame {frame}");
         }
+        let camera = scene.camera.build(settings.aspect_ratio(), frame as f64);
+
+        let framebuffer = render(
+            camera.as_ref(),
+            &world,
+            &lights,
+            scene.environment.as_ref(),
+            &settings,
+            cli.thread_count(),
+            TILE_SIZE,
+        );
+
+        write_image(&path, format, &framebuffer, &settings)?;
     }
 
-    world.add(Box::new(Sphere::new(
-        Vec3d::new(0.0, 1.0, 0.0),
-        1.0,
-        Box::new(Dielectric::new(1.5)),
-    )));
-
-    world.add(Box::new(Sphere::new(
-        Vec3d::new(-4.0, 1.0, 0.0),
-        1.0,
-        Box::new(Lambertian::new(Vec3d::new(0.4, 0.2, 0.1))),
-    )));
-
-    world.add(Box::new(Sphere::new(
-        Vec3d::new(4.0, 1.0, 0.0),
-        1.0,
-        Box::new(Metal::new(Vec3d::new(0.7, 0.6, 0.5), 0.0)),
-    )));
-
-    world
-}
-
-fn gen_random() -> f64 {
-    rand::thread_rng().gen_range(0.0..1.0)
-}
-
-fn gen_random_range(low: f64, high: f64) -> f64 {
-    rand::thread_rng().gen_range(low..high)
+    Ok(())
 }
@@ -1,65 +1,236 @@
+use std::f64::consts::PI;
+
 use crate::*;
 
 pub struct ScatterRecord {
+    // bsdf times cosine divided by the pdf, the factor the path throughput is multiplied by
     pub attenuation: Colour,
     pub scattered: Ray,
+    // solid angle density of the scattered direction, unused for specular bounces
+    pub pdf: f64,
+    // specular bounces follow a single direction, so light sampling cannot help them
+    pub is_specular: bool,
 }
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord>;
+// `outgoing` points back along the incoming ray and `incoming` towards where light arrives from,
+// both normalised
+pub trait Material: Send + Sync {
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord>;
+
+    // bsdf times the cosine of `incoming` with the normal; black for purely specular materials
+    fn eval(&self, _hit_record: &HitRecord, _outgoing: Vec3d, _incoming: Vec3d) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    // solid angle density of `sample` choosing `incoming`
+    fn pdf(&self, _hit_record: &HitRecord, _outgoing: Vec3d, _incoming: Vec3d) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // whether every bounce follows a single direction, as `sample` marks its records, known
+    // before sampling so light sampling does not depend on a sample succeeding
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // whether this scatters inside a volume rather than off a surface, as media require
+    fn is_phase_function(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
-    albedo: Colour,
+    albedo: Box<dyn Texture>,
 }
 
 impl Lambertian {
-    pub const fn new(albedo: Colour) -> Self {
+    pub fn new(albedo: Colour) -> Self {
+        Self::with_texture(Box::new(SolidColour::new(albedo)))
+    }
+
+    pub fn with_texture(albedo: Box<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let mut scatter_direction = hit_record.normal + random_new_vec3d();
-
-        if scatter_direction.min_component().abs() < 1.0e-8 {
-            scatter_direction = hit_record.normal;
-        }
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let local = cosine_hemisphere(sampler.get_2d());
+        let scatter_direction = local_to_world(local, hit_record.normal);
 
-        let scattered = Ray::new(hit_record.point, scatter_direction);
-        let attenuation = self.albedo;
+        let scattered = Ray::new(hit_record.point, scatter_direction, ray.time);
+        let pdf = cosine_hemisphere_pdf(local.z);
+        // the cosine weighted pdf cancels with the cosine and 1/pi of the bsdf
+        let attenuation = self
+            .albedo
+            .value(hit_record.u, hit_record.v, hit_record.point);
         Some(ScatterRecord {
             attenuation,
             scattered,
+            pdf,
+            is_specular: false,
         })
     }
+
+    fn eval(&self, hit_record: &HitRecord, _outgoing: Vec3d, incoming: Vec3d) -> Colour {
+        let cosine = incoming.dot(hit_record.normal).max(0.0);
+        self.albedo
+            .value(hit_record.u, hit_record.v, hit_record.point)
+            * (cosine / PI)
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, _outgoing: Vec3d, incoming: Vec3d) -> f64 {
+        cosine_hemisphere_pdf(incoming.dot(hit_record.normal))
+    }
 }
 
+// a GGX microfacet conductor with `fuzz` as the roughness, tinted by a Schlick Fresnel term
 pub struct Metal {
-    albedo: Colour,
+    albedo: Box<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
-    pub const fn new(albedo: Colour, fuzz: f64) -> Self {
-        Self { albedo, fuzz }
+    pub fn new(albedo: Colour, fuzz: f64) -> Self {
+        Self::with_texture(Box::new(SolidColour::new(albedo)), fuzz)
+    }
+
+    pub fn with_texture(albedo: Box<dyn Texture>, fuzz: f64) -> Self {
+        Self {
+            albedo,
+            fuzz: fuzz.clamp(0.0, 1.0),
+        }
+    }
+
+    fn is_mirror(&self) -> bool {
+        self.fuzz < MIN_ROUGHNESS
+    }
+
+    fn distribution(&self, cos_half: f64) -> f64 {
+        let alpha_squared = self.fuzz * self.fuzz;
+        let denominator = cos_half * cos_half * (alpha_squared - 1.0) + 1.0;
+        alpha_squared / (PI * denominator * denominator)
+    }
+
+    // Smith masking for a single direction
+    fn masking(&self, cosine: f64) -> f64 {
+        let alpha_squared = self.fuzz * self.fuzz;
+        2.0 * cosine / (cosine + (alpha_squared + (1.0 - alpha_squared) * cosine * cosine).sqrt())
+    }
+
+    fn fresnel(&self, hit_record: &HitRecord, cosine: f64) -> Colour {
+        let f0 = self
+            .albedo
+            .value(hit_record.u, hit_record.v, hit_record.point);
+        f0 + (Colour::new(1.0, 1.0, 1.0) - f0) * (1.0 - cosine).max(0.0).powi(5)
     }
 }
 
+// below this roughness the lobe is treated as a perfect mirror
+const MIN_ROUGHNESS: f64 = 1.0e-3;
+
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let reflected = reflect_on_normal(ray.direction, hit_record.normal);
-        let scattered = Ray::new(hit_record.point, reflected + self.fuzz * random_new_vec3d());
-        let attenuation = self.albedo;
-        if scattered.direction.dot(hit_record.normal) > 0.0 {
-            Some(ScatterRecord {
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let outgoing = -ray.direction.normalise();
+        let normal = hit_record.normal;
+
+        if self.is_mirror() {
+            let reflected = reflect_on_normal(ray.direction, normal);
+            let attenuation = self
+                .albedo
+                .value(hit_record.u, hit_record.v, hit_record.point);
+            return Some(ScatterRecord {
                 attenuation,
-                scattered,
-            })
-        } else {
-            None
+                scattered: Ray::new(hit_record.point, reflected, ray.time),
+                pdf: 0.0,
+                is_specular: true,
+            });
         }
+
+        // picks a microfacet normal from the distribution and mirrors about it
+        let (random, random_phi) = sampler.get_2d();
+        let cos_theta_squared = (1.0 - random) / (random * (self.fuzz * self.fuzz - 1.0) + 1.0);
+        let cos_theta = cos_theta_squared.sqrt();
+        let sin_theta = (1.0 - cos_theta_squared).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_phi;
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let half = tangent * (phi.cos() * sin_theta)
+            + bitangent * (phi.sin() * sin_theta)
+            + normal * cos_theta;
+
+        let incoming = reflect_on_normal(-outgoing, half);
+        let cos_outgoing = outgoing.dot(normal);
+        let cos_incoming = incoming.dot(normal);
+        let cos_outgoing_half = outgoing.dot(half);
+        if cos_outgoing <= 0.0 || cos_incoming <= 0.0 || cos_outgoing_half <= 0.0 {
+            return None;
+        }
+
+        // the distribution term cancels, leaving Fresnel, masking and the jacobian
+        let attenuation = self.fresnel(hit_record, cos_outgoing_half)
+            * (self.masking(cos_outgoing) * self.masking(cos_incoming) * cos_outgoing_half
+                / (cos_outgoing * cos_theta));
+
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Ray::new(hit_record.point, incoming, ray.time),
+            pdf: self.pdf(hit_record, outgoing, incoming),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, hit_record: &HitRecord, outgoing: Vec3d, incoming: Vec3d) -> Colour {
+        let normal = hit_record.normal;
+        let cos_outgoing = outgoing.dot(normal);
+        let cos_incoming = incoming.dot(normal);
+        if self.is_mirror() || cos_outgoing <= 0.0 || cos_incoming <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+
+        let half = (outgoing + incoming).normalise();
+        let specular = self.distribution(half.dot(normal))
+            * self.masking(cos_outgoing)
+            * self.masking(cos_incoming)
+            / (4.0 * cos_outgoing);
+        self.fresnel(hit_record, incoming.dot(half)) * specular
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, outgoing: Vec3d, incoming: Vec3d) -> f64 {
+        let normal = hit_record.normal;
+        if self.is_mirror() || outgoing.dot(normal) <= 0.0 || incoming.dot(normal) <= 0.0 {
+            return 0.0;
+        }
+
+        let half = (outgoing + incoming).normalise();
+        let cos_half = half.dot(normal);
+        self.distribution(cos_half) * cos_half / (4.0 * outgoing.dot(half))
+    }
+
+    fn is_specular(&self) -> bool {
+        self.is_mirror()
     }
 }
 
@@ -74,7 +245,12 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         let attenuation = Colour::new(1.0, 1.0, 1.0);
         let refraction_ratio = if hit_record.front_face {
             1.0 / self.refraction_index
@@ -89,19 +265,163 @@ impl Material for Dielectric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
-        let direction = if cannot_refract
-            || reflectance(cos_theta, refraction_ratio) > rand::thread_rng().gen_range(0.0..1.0)
-        {
-            reflect_on_normal(unit_direction, hit_record.normal)
-        } else {
-            refract(unit_direction, hit_record.normal, refraction_ratio)
-        };
+        let direction =
+            if cannot_refract || reflectance(cos_theta, refraction_ratio) > sampler.get_1d() {
+                reflect_on_normal(unit_direction, hit_record.normal)
+            } else {
+                refract(unit_direction, hit_record.normal, refraction_ratio)
+            };
 
-        let scattered = Ray::new(hit_record.point, direction);
+        let scattered = Ray::new(hit_record.point, direction, ray.time);
 
         Some(ScatterRecord {
             attenuation,
             scattered,
+            pdf: 0.0,
+            is_specular: true,
         })
     }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+// scatters equally in all directions, for the inside of participating media
+pub struct Isotropic {
+    albedo: Colour,
+}
+
+impl Isotropic {
+    pub const fn new(albedo: Colour) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::new(hit_record.point, uniform_sphere(sampler.get_2d()), ray.time),
+            pdf: uniform_sphere_pdf(),
+            is_specular: false,
+        })
+    }
+
+    // there is no cosine term inside a volume
+    fn eval(&self, _hit_record: &HitRecord, _outgoing: Vec3d, _incoming: Vec3d) -> Colour {
+        self.albedo / (4.0 * PI)
+    }
+
+    fn pdf(&self, _hit_record: &HitRecord, _outgoing: Vec3d, _incoming: Vec3d) -> f64 {
+        uniform_sphere_pdf()
+    }
+
+    fn is_phase_function(&self) -> bool {
+        true
+    }
+}
+
+// scatters mostly forwards for positive `g` and backwards for negative, like smoke and clouds
+pub struct HenyeyGreenstein {
+    albedo: Colour,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Colour, g: f64) -> Self {
+        Self {
+            albedo,
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    // density of turning by an angle with `cosine` away from the direction of travel
+    fn phase(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn sample(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let g = self.g;
+        let (random, random_phi) = sampler.get_2d();
+        let cos_theta = if g.abs() < 1.0e-3 {
+            1.0 - 2.0 * random
+        } else {
+            let term = (1.0 - g * g) / (1.0 - g + 2.0 * g * random);
+            ((1.0 + g * g - term * term) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_phi;
+
+        let forward = ray.direction.normalise();
+        let (u, v) = orthonormal_basis(forward);
+        let direction =
+            u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + forward * cos_theta;
+
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::new(hit_record.point, direction, ray.time),
+            pdf: self.phase(cos_theta),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, _hit_record: &HitRecord, outgoing: Vec3d, incoming: Vec3d) -> Colour {
+        self.albedo * self.phase(-outgoing.dot(incoming))
+    }
+
+    fn pdf(&self, _hit_record: &HitRecord, outgoing: Vec3d, incoming: Vec3d) -> f64 {
+        self.phase(-outgoing.dot(incoming))
+    }
+
+    fn is_phase_function(&self) -> bool {
+        true
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Colour,
+}
+
+impl DiffuseLight {
+    pub const fn new(emit: Colour) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(
+        &self,
+        _ray: &Ray,
+        _hit_record: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Colour {
+        if hit_record.front_face {
+            self.emit
+        } else {
+            Colour::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
 }
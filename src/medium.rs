@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use crate::*;
+
+// a volume of constant density inside a closed boundary, scattering with `phase_function`; the
+// boundary must also be convex, as the ray is taken to be inside from its first crossing to its
+// second and outside beyond that
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Box<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl ConstantMedium {
+    // `random` supplies the number the free-flight distance is drawn from
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        random: &mut dyn FnMut() -> f64,
+    ) -> Option<HitRecord<'_>> {
+        // the span of the ray inside the boundary, which may start behind the origin
+        let entry = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(ray, entry.t + 1.0e-4, f64::INFINITY)?;
+
+        let t_entry = entry.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_entry >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (t_exit - t_entry) * ray_length;
+        let hit_distance = self.neg_inv_density * (1.0 - random()).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_entry + hit_distance / ray_length;
+        // scattering does not depend on the normal, so any direction will do
+        let mut hit_record = HitRecord::new(
+            ray.at(t),
+            Vec3d::new(1.0, 0.0, 0.0),
+            t,
+            self.phase_function.as_ref(),
+        );
+        hit_record.front_face = true;
+        Some(hit_record)
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.sample_hit(ray, t_min, t_max, &mut gen_random)
+    }
+
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        self.sample_hit(ray, t_min, t_max, &mut || sampler.get_1d())
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+// a volume whose density is read from a voxel grid stretched over `bounds`, sampled with delta
+// tracking against the densest voxel
+pub struct GridMedium {
+    bounds: Aabb,
+    grid: Arc<DensityGrid>,
+    density_scale: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl GridMedium {
+    pub fn new(
+        bounds: Aabb,
+        grid: Arc<DensityGrid>,
+        density_scale: f64,
+        phase_function: Box<dyn Material>,
+    ) -> Self {
+        Self {
+            bounds,
+            grid,
+            density_scale,
+            phase_function,
+        }
+    }
+
+    fn density(&self, point: Vec3d) -> f64 {
+        let extent = self.bounds.maximum - self.bounds.minimum;
+        let local = point - self.bounds.minimum;
+        let unit = Vec3d::new(local.x / extent.x, local.y / extent.y, local.z / extent.z);
+        self.grid.density(unit) * self.density_scale
+    }
+
+    // `random` supplies the numbers for every tentative collision and its acceptance
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        random: &mut dyn FnMut() -> f64,
+    ) -> Option<HitRecord<'_>> {
+        let majorant = self.grid.max_density * self.density_scale;
+        if majorant <= 0.0 {
+            return None;
+        }
+        let (t_entry, t_exit) = self.bounds.intersect(ray, t_min, t_max)?;
+
+        // step through a medium as dense as the densest voxel, and accept a collision as real in
+        // proportion to the actual density there
+        let ray_length = ray.direction.length();
+        let mut t = t_entry;
+        loop {
+            t -= (1.0 - random()).ln() / (majorant * ray_length);
+            if t >= t_exit {
+                return None;
+            }
+
+            let point = ray.at(t);
+            if random() * majorant < self.density(point) {
+                let mut hit_record = HitRecord::new(
+                    point,
+                    Vec3d::new(1.0, 0.0, 0.0),
+                    t,
+                    self.phase_function.as_ref(),
+                );
+                hit_record.front_face = true;
+                return Some(hit_record);
+            }
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.sample_hit(ray, t_min, t_max, &mut gen_random)
+    }
+
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        self.sample_hit(ray, t_min, t_max, &mut || sampler.get_1d())
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
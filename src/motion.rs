@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use crate::*;
+
+// moves `object` in a straight line by `displacement` between `time0` and `time1`, resting at
+// either end outside that interval
+pub struct LinearMotion {
+    object: Arc<dyn Hittable>,
+    displacement: Vec3d,
+    time0: f64,
+    time1: f64,
+}
+
+impl LinearMotion {
+    pub fn new(object: Arc<dyn Hittable>, displacement: Vec3d, time0: f64, time1: f64) -> Self {
+        Self {
+            object,
+            displacement,
+            time0,
+            time1,
+        }
+    }
+
+    fn offset(&self, time: f64) -> Vec3d {
+        let progress = if self.time1 > self.time0 {
+            ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0)
+        } else if time < self.time0 {
+            0.0
+        } else {
+            1.0
+        };
+        self.displacement * progress
+    }
+}
+
+impl Hittable for LinearMotion {
+    // moves the ray back instead of moving the object forward
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let offset = self.offset(ray.time);
+        let moved_ray = Ray::new(ray.origin - offset, ray.direction, ray.time);
+
+        let mut hit_record = self.object.hit(&moved_ray, t_min, t_max)?;
+        hit_record.point += offset;
+        Some(hit_record)
+    }
+
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        let offset = self.offset(ray.time);
+        let moved_ray = Ray::new(ray.origin - offset, ray.direction, ray.time);
+
+        let mut hit_record = self.object.hit_sampled(&moved_ray, t_min, t_max, sampler)?;
+        hit_record.point += offset;
+        Some(hit_record)
+    }
+
+    // covers the object at both ends of its path
+    fn bounding_box(&self) -> Aabb {
+        let start = self.object.bounding_box();
+        let end = Aabb::new(
+            start.minimum + self.displacement,
+            start.maximum + self.displacement,
+        );
+        start.surrounding(end)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    fn sample_point(&self, origin: Vec3d, time: f64, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        let offset = self.offset(time);
+        let point = self.object.sample_point(origin - offset, time, sampler)?;
+        Some(point + offset)
+    }
+
+    fn pdf_value(&self, origin: Vec3d, direction: Vec3d, time: f64) -> f64 {
+        self.object
+            .pdf_value(origin - self.offset(time), direction, time)
+    }
+}
@@ -0,0 +1,641 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::*;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Mesh {
+        path: PathBuf,
+        source: MeshError,
+    },
+    Empty {
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            Self::Mesh { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Empty { path } => write!(f, "{}: no faces", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+            Self::Mesh { source, .. } => Some(source),
+            Self::Empty { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub diffuse: Colour,
+    pub diffuse_map: Option<Arc<Image>>,
+    pub specular: Colour,
+    pub emission: Colour,
+    pub shininess: f64,
+    pub refraction_index: f64,
+    pub dissolve: f64,
+    pub illum: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Colour::new(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Colour::new(0.0, 0.0, 0.0),
+            emission: Colour::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // maps the MTL illumination model onto the closest renderer material
+    pub fn to_material(&self) -> Box<dyn Material> {
+        if self.emission.max_component() > 0.0 {
+            return Box::new(DiffuseLight::new(self.emission));
+        }
+
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        if transparent {
+            let refraction_index = if self.refraction_index > 1.0 {
+                self.refraction_index
+            } else {
+                1.5
+            };
+            return Box::new(Dielectric::new(refraction_index));
+        }
+
+        let reflective = matches!(self.illum, 3 | 5 | 8);
+        if reflective && self.specular.max_component() > 0.0 {
+            // Phong exponent to a roughness in [0, 1]
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+            return Box::new(Metal::new(self.specular, fuzz));
+        }
+
+        match &self.diffuse_map {
+            Some(image) => Box::new(Lambertian::with_texture(Box::new(ImageTexture::new(
+                image.clone(),
+                WrapMode::Repeat,
+            )))),
+            None => Box::new(Lambertian::new(self.diffuse)),
+        }
+    }
+}
+
+struct LineParser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl LineParser<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn float(&self, token: Option<&str>, what: &str) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {what}")))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {what} `{token}`")))
+    }
+
+    fn vec3(&self, tokens: &mut std::str::SplitWhitespace, what: &str) -> Result<Vec3d, ObjError> {
+        Ok(Vec3d::new(
+            self.float(tokens.next(), what)?,
+            self.float(tokens.next(), what)?,
+            self.float(tokens.next(), what)?,
+        ))
+    }
+
+    fn colour(
+        &self,
+        tokens: &mut std::str::SplitWhitespace,
+        what: &str,
+    ) -> Result<Colour, ObjError> {
+        let r = self.float(tokens.next(), what)?;
+        match tokens.next() {
+            // a single value is a grey level
+            None => Ok(Colour::new(r, r, r)),
+            Some(g) => Ok(Colour::new(
+                r,
+                self.float(Some(g), what)?,
+                self.float(tokens.next(), what)?,
+            )),
+        }
+    }
+
+    // resolves a 1-based or negative (relative) OBJ index into a 0-based one
+    fn index(&self, token: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+        let value: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {what} index `{token}`")))?;
+        let resolved = if value < 0 {
+            count as i64 + value
+        } else {
+            value - 1
+        };
+        if value == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(format!("{what} index {value} out of range")));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let contents = read_file(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = HashMap::new();
+    let mut images: HashMap<String, Arc<Image>> = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (line_index, line) in contents.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: line_index + 1,
+        };
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| parser.error("missing material name"))?;
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name.to_string(), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            return Err(parser.error(format!("`{keyword}` before any `newmtl`")));
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parser.colour(&mut tokens, "Kd colour")?,
+            "map_Kd" => {
+                // options such as `-bm` are not supported, the file name comes last
+                let name = tokens
+                    .last()
+                    .ok_or_else(|| parser.error("missing texture file name"))?;
+                let image = match images.get(name) {
+                    Some(image) => image.clone(),
+                    None => {
+                        let image = Image::load(&directory.join(name))
+                            .map_err(|error| parser.error(error.to_string()))?;
+                        let image = Arc::new(image);
+                        images.insert(name.to_string(), image.clone());
+                        image
+                    }
+                };
+                material.diffuse_map = Some(image);
+            }
+            "Ks" => material.specular = parser.colour(&mut tokens, "Ks colour")?,
+            "Ke" => material.emission = parser.colour(&mut tokens, "Ke colour")?,
+            "Ns" => material.shininess = parser.float(tokens.next(), "Ns exponent")?,
+            "Ni" => material.refraction_index = parser.float(tokens.next(), "Ni index")?,
+            "d" => material.dissolve = parser.float(tokens.next(), "dissolve")?,
+            "Tr" => material.dissolve = 1.0 - parser.float(tokens.next(), "transparency")?,
+            "illum" => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| parser.error("missing illumination model"))?;
+                material.illum = token
+                    .parse()
+                    .map_err(|_| parser.error(format!("invalid illumination model `{token}`")))?;
+            }
+            // other texture maps and statements are not supported yet
+            _ => {}
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    material: Option<String>,
+    positions: Vec<Vec3d>,
+    normals: Vec<Option<Vec3d>>,
+    uvs: Vec<Option<Vec2d>>,
+    indices: Vec<[usize; 3]>,
+    vertex_lookup: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+}
+
+impl MeshBuilder {
+    fn vertex(
+        &mut self,
+        key: (usize, Option<usize>, Option<usize>),
+        positions: &[Vec3d],
+        uvs: &[Vec2d],
+        normals: &[Vec3d],
+    ) -> usize {
+        *self.vertex_lookup.entry(key).or_insert_with(|| {
+            self.positions.push(positions[key.0]);
+            self.uvs.push(key.1.map(|index| uvs[index]));
+            self.normals.push(key.2.map(|index| normals[index]));
+            self.positions.len() - 1
+        })
+    }
+
+    // per-vertex attributes are only kept when every vertex of the mesh has them
+    fn build(
+        self,
+        path: &Path,
+        materials: &HashMap<String, MtlMaterial>,
+    ) -> Result<Option<TriangleMesh>, ObjError> {
+        if self.indices.is_empty() {
+            return Ok(None);
+        }
+
+        let normals = self.normals.into_iter().collect::<Option<Vec<_>>>();
+        let uvs = self.uvs.into_iter().collect::<Option<Vec<_>>>();
+        let material = self
+            .material
+            .and_then(|name| materials.get(&name).cloned())
+            .unwrap_or_default();
+
+        TriangleMesh::new(
+            self.positions,
+            normals.unwrap_or_default(),
+            uvs.unwrap_or_default(),
+            self.indices,
+            material.to_material(),
+        )
+        .map(Some)
+        .map_err(|source| ObjError::Mesh {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+// loads every object, group and material run of an OBJ file as a separate triangle mesh
+pub fn load_obj(path: &Path) -> Result<HittableList, ObjError> {
+    let contents = read_file(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    let mut normals = vec![];
+    let mut materials = HashMap::new();
+
+    let mut world = HittableList::new();
+    let mut builder = MeshBuilder::default();
+
+    for (line_index, line) in contents.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: line_index + 1,
+        };
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parser.vec3(&mut tokens, "vertex position")?),
+            "vn" => normals.push(parser.vec3(&mut tokens, "vertex normal")?),
+            "vt" => {
+                let u = parser.float(tokens.next(), "texture coordinate")?;
+                let v = match tokens.next() {
+                    Some(token) => parser.float(Some(token), "texture coordinate")?,
+                    None => 0.0,
+                };
+                uvs.push(Vec2d::new(u, v));
+            }
+            "f" => {
+                let mut corners = vec![];
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position =
+                        parser.index(parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                    let uv = match parts.next() {
+                        Some("") | None => None,
+                        Some(part) => Some(parser.index(part, uvs.len(), "texture coordinate")?),
+                    };
+                    let normal = match parts.next() {
+                        Some("") | None => None,
+                        Some(part) => Some(parser.index(part, normals.len(), "normal")?),
+                    };
+                    corners.push(builder.vertex(
+                        (position, uv, normal),
+                        &positions,
+                        &uvs,
+                        &normals,
+                    ));
+                }
+
+                if corners.len() < 3 {
+                    return Err(parser.error("face needs at least three vertices"));
+                }
+
+                // polygons are triangulated as a fan around the first vertex
+                for i in 1..corners.len() - 1 {
+                    builder
+                        .indices
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let material = builder.material.clone();
+                if let Some(mesh) = std::mem::take(&mut builder).build(path, &materials)? {
+                    world.add(Arc::new(mesh));
+                }
+                builder.material = material;
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| parser.error("missing material name"))?;
+                if !materials.contains_key(name) {
+                    return Err(parser.error(format!("unknown material `{name}`")));
+                }
+                if let Some(mesh) = std::mem::take(&mut builder).build(path, &materials)? {
+                    world.add(Arc::new(mesh));
+                }
+                builder.material = Some(name.to_string());
+            }
+            "mtllib" => {
+                for library in tokens {
+                    materials.extend(load_mtl(&directory.join(library))?);
+                }
+            }
+            // smoothing groups, lines, points and free-form geometry are ignored
+            _ => {}
+        }
+    }
+
+    if let Some(mesh) = builder.build(path, &materials)? {
+        world.add(Arc::new(mesh));
+    }
+
+    if world.is_empty() {
+        return Err(ObjError::Empty {
+            path: path.to_path_buf(),
+        });
+    }
+
+    Ok(world)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    // a directory of `files` unique to one test run, removed again when dropped
+    struct ScratchDirectory(PathBuf);
+
+    impl ScratchDirectory {
+        fn new(test: &str, files: &[(&str, &str)]) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let directory =
+                std::env::temp_dir().join(format!("obj_{test}_{}_{unique}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+            for (name, contents) in files {
+                std::fs::write(directory.join(name), contents).unwrap();
+            }
+            Self(directory)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for ScratchDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // shoots straight down the z axis at (x, y)
+    fn hit_at(world: &HittableList, x: f64, y: f64) -> Option<HitRecord<'_>> {
+        let ray = Ray::new(Vec3d::new(x, y, 1.0), Vec3d::new(0.0, 0.0, -1.0), 0.0);
+        world.hit(&ray, 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_latest_vertex() {
+        let directory = ScratchDirectory::new(
+            "negative",
+            &[(
+                "mesh.obj",
+                "v 5 5 5\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n",
+            )],
+        );
+        let path = directory.path("mesh.obj");
+        let world = load_obj(&path).unwrap();
+
+        let bounding_box = world.bounding_box();
+        assert!(bounding_box.maximum.max_component() < 1.1);
+        assert_eq!(hit_at(&world, 0.25, 0.25).map(|hit| hit.t), Some(1.0));
+    }
+
+    #[test]
+    fn faces_read_texture_coordinates_and_normals() {
+        let directory = ScratchDirectory::new(
+            "corners",
+            &[(
+                "mesh.obj",
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                 v 2 0 0\nv 3 0 0\nv 2 1 0\n\
+                 v 4 0 0\nv 5 0 0\nv 4 1 0\n\
+                 vt 0 0\nvt 2 0\nvt 0 2\n\
+                 vn 0 1 1\n\
+                 o full\nf 1/1/1 2/2/1 3/3/1\n\
+                 o normal_only\nf 4//1 5//1 6//1\n\
+                 o uv_only\nf 7/1 8/2 9/3\n",
+            )],
+        );
+        let path = directory.path("mesh.obj");
+        let world = load_obj(&path).unwrap();
+        let tilted = Vec3d::new(0.0, 1.0, 1.0).normalise();
+
+        let hit = hit_at(&world, 0.25, 0.25).unwrap();
+        assert!((hit.u - 0.5).abs() < 1.0e-9 && (hit.v - 0.5).abs() < 1.0e-9);
+        assert!((hit.normal - tilted).length() < 1.0e-9);
+
+        let hit = hit_at(&world, 2.25, 0.25).unwrap();
+        assert!((hit.normal - tilted).length() < 1.0e-9);
+
+        let hit = hit_at(&world, 4.25, 0.25).unwrap();
+        assert!((hit.u - 0.5).abs() < 1.0e-9 && (hit.v - 0.5).abs() < 1.0e-9);
+        assert!((hit.normal - Vec3d::new(0.0, 0.0, 1.0)).length() < 1.0e-9);
+    }
+
+    #[test]
+    fn usemtl_starts_a_new_mesh_with_that_material() {
+        let directory = ScratchDirectory::new(
+            "usemtl",
+            &[
+                (
+                    "mesh.obj",
+                    "mtllib mesh.mtl\n\
+                     v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                     v 2 0 0\nv 3 0 0\nv 2 1 0\n\
+                     usemtl matte\nf 1 2 3\n\
+                     usemtl glow\nf 4 5 6\n",
+                ),
+                (
+                    "mesh.mtl",
+                    "newmtl matte\nKd 0.5 0.5 0.5\nnewmtl glow\nKe 1 1 1\n",
+                ),
+            ],
+        );
+        let path = directory.path("mesh.obj");
+        let world = load_obj(&path).unwrap();
+        assert_eq!(world.into_objects().len(), 2);
+
+        let world = load_obj(&path).unwrap();
+        assert!(!hit_at(&world, 0.25, 0.25).unwrap().material.is_emissive());
+        assert!(hit_at(&world, 2.25, 0.25).unwrap().material.is_emissive());
+    }
+
+    #[test]
+    fn usemtl_rejects_unknown_materials() {
+        let directory = ScratchDirectory::new(
+            "unknown_material",
+            &[(
+                "mesh.obj",
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n",
+            )],
+        );
+        let path = directory.path("mesh.obj");
+        assert!(matches!(
+            load_obj(&path),
+            Err(ObjError::Parse { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn files_without_faces_are_rejected() {
+        let directory = ScratchDirectory::new("empty", &[("empty.obj", "")]);
+        let path = directory.path("empty.obj");
+        assert!(matches!(load_obj(&path), Err(ObjError::Empty { .. })));
+
+        let directory =
+            ScratchDirectory::new("vertices_only", &[("points.obj", "v 0 0 0\nv 1 0 0\n")]);
+        let path = directory.path("points.obj");
+        assert!(matches!(load_obj(&path), Err(ObjError::Empty { .. })));
+    }
+
+    // loads an MTL file and builds the named materials in order
+    fn mtl_materials(test: &str, mtl: &str, names: &[&str]) -> Vec<Box<dyn Material>> {
+        let directory = ScratchDirectory::new(test, &[("mesh.mtl", mtl)]);
+        let materials = load_mtl(&directory.path("mesh.mtl")).unwrap();
+        names
+            .iter()
+            .map(|name| materials[*name].to_material())
+            .collect()
+    }
+
+    // a surface point facing up the z axis, as a material sees it
+    fn facing_up(material: &dyn Material) -> HitRecord<'_> {
+        let mut hit_record = HitRecord::new(Vec3d::ZERO, Vec3d::Z, 1.0, material);
+        hit_record.set_face_normal(&Ray::new(Vec3d::Z, -Vec3d::Z, 0.0), Vec3d::Z);
+        hit_record
+    }
+
+    #[test]
+    fn transparent_mtl_materials_refract() {
+        seed_thread_rng(1);
+        let materials = mtl_materials(
+            "dielectric",
+            "newmtl glass\nKd 0.8 0.8 0.8\nNi 2.0\nd 0.5\n\
+             newmtl illum\nKd 0.8 0.8 0.8\nillum 7\n",
+            &["glass", "illum"],
+        );
+
+        // light arriving 45 degrees off the normal bends to sin(45) / Ni
+        let incoming = Vec3d::new(1.0, 0.0, -1.0).normalise();
+        for (material, refraction_index) in materials.iter().zip([2.0, 1.5]) {
+            let hit_record = facing_up(material.as_ref());
+            let ray = Ray::new(-incoming, incoming, 0.0);
+            let refracted = (0..100)
+                .filter_map(|_| material.sample(&ray, &hit_record, &mut IndependentSampler))
+                .inspect(|scatter| assert!(scatter.is_specular))
+                .map(|scatter| scatter.scattered.direction.normalise())
+                .find(|direction| direction.z < 0.0)
+                .unwrap();
+            let sin_refracted = (1.0 - refracted.z * refracted.z).sqrt();
+            assert!((sin_refracted - 0.5_f64.sqrt() / refraction_index).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn reflective_mtl_materials_become_metal() {
+        let materials = mtl_materials(
+            "metal",
+            "newmtl polished\nKd 0.8 0.8 0.8\nKs 0.9 0.9 0.9\nNs 1000\nillum 3\n\
+             newmtl matte\nKd 0.8 0.8 0.8\nKs 0.9 0.9 0.9\nNs 1000\nillum 2\n",
+            &["polished", "matte"],
+        );
+
+        // a metal reflects far more along the mirror direction than straight up, a diffuse
+        // surface the other way round
+        let outgoing = Vec3d::new(-1.0, 0.0, 1.0).normalise();
+        let mirror = Vec3d::new(1.0, 0.0, 1.0).normalise();
+        let lobe_ratio = |material: &dyn Material| {
+            let hit_record = facing_up(material);
+            material.eval(&hit_record, outgoing, mirror).x
+                / material.eval(&hit_record, outgoing, Vec3d::Z).x
+        };
+        assert!(lobe_ratio(materials[0].as_ref()) > 100.0);
+        assert!(lobe_ratio(materials[1].as_ref()) < 1.0);
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Plain text 8-bit PPM (P3)
+    Ppm,
+    /// Binary 8-bit PPM (P6)
+    PpmBinary,
+    /// Linear 32-bit float PFM, without gamma or clamping
+    Pfm,
+}
+
+impl OutputFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ppm" => Some(Self::Ppm),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+}
+
+// `image.ppm` becomes `image_0001.ppm` for frame 1, so frames sort in order
+pub fn numbered_path(path: &Path, frame: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}_{frame:04}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{frame:04}"),
+    };
+    path.with_file_name(name)
+}
+
+pub fn write_image(
+    path: &Path,
+    format: OutputFormat,
+    framebuffer: &[Colour],
+    settings: &RenderSettings,
+) -> std::io::Result<()> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    // averages the samples and applies the exposure in one step
+    let scale = settings.exposure / settings.samples_per_pixel as f64;
+
+    match format {
+        OutputFormat::Ppm => {
+            file.write_all(format!("P3\n{width} {height}\n255\n").as_ref())?;
+            for pixel_colour in framebuffer {
+                pixel_colour.write_ppm(&mut file, scale);
+            }
+        }
+        OutputFormat::PpmBinary => {
+            file.write_all(format!("P6\n{width} {height}\n255\n").as_ref())?;
+            for pixel_colour in framebuffer {
+                file.write_all(&pixel_colour.to_rgb8(scale))?;
+            }
+        }
+        OutputFormat::Pfm => {
+            // a negative scale marks little endian data, and rows are stored bottom first
+            file.write_all(format!("PF\n{width} {height}\n-1.0\n").as_ref())?;
+            for row in framebuffer.chunks(width as usize).rev() {
+                for pixel_colour in row {
+                    for channel in 0..3 {
+                        let value = (pixel_colour[channel] * scale) as f32;
+                        file.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+    }
+
+    file.flush()
+}
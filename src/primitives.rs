@@ -1,3 +1,7 @@
+pub use quad::*;
 pub use sphere::*;
+pub use triangle::*;
 
+mod quad;
 mod sphere;
+mod triangle;
@@ -0,0 +1,222 @@
+use crate::*;
+use std::borrow::Borrow;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum QuadError {
+    Degenerate,
+}
+
+impl fmt::Display for QuadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Degenerate => write!(f, "quad sides must be non-zero and not parallel"),
+        }
+    }
+}
+
+impl std::error::Error for QuadError {}
+
+pub struct Quad {
+    corner: Vec3d,
+    u: Vec3d,
+    v: Vec3d,
+    w: Vec3d,
+    normal: Vec3d,
+    d: f64,
+    area: f64,
+    material: Box<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(
+        corner: Vec3d,
+        u: Vec3d,
+        v: Vec3d,
+        material: Box<dyn Material>,
+    ) -> Result<Self, QuadError> {
+        let n = u.cross(v);
+        // the squared sine of the angle between the sides, which is NaN for a zero side
+        let sine_squared = n.length_squared() / (u.length_squared() * v.length_squared());
+        if sine_squared.is_nan() || sine_squared <= 1.0e-12 {
+            return Err(QuadError::Degenerate);
+        }
+
+        let normal = n.normalise();
+        let d = normal.dot(corner);
+        let w = n / n.dot(n);
+        let area = n.length();
+
+        Ok(Self {
+            corner,
+            u,
+            v,
+            w,
+            normal,
+            d,
+            area,
+            material,
+        })
+    }
+}
+
+// below this solid angle the spherical triangle warp loses precision and area sampling is as good,
+// and above it the quad nearly fills the hemisphere and the warp becomes unstable
+const MIN_SPHERICAL_SOLID_ANGLE: f64 = 3.0e-4;
+const MAX_SPHERICAL_SOLID_ANGLE: f64 = 6.22;
+
+impl Quad {
+    // the corners as unit directions from `origin`, split into the triangles 0 1 2 and 0 2 3,
+    // with the solid angle of each; none when the quad should be area sampled instead
+    fn spherical_triangles(&self, origin: Vec3d) -> Option<([Vec3d; 4], f64, f64)> {
+        let corners = [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.u + self.v,
+            self.corner + self.v,
+        ]
+        .map(|corner| (corner - origin).normalise());
+
+        let first = spherical_triangle_area(corners[0], corners[1], corners[2]);
+        let second = spherical_triangle_area(corners[0], corners[2], corners[3]);
+        let solid_angle = first + second;
+        if !(MIN_SPHERICAL_SOLID_ANGLE..=MAX_SPHERICAL_SOLID_ANGLE).contains(&solid_angle) {
+            return None;
+        }
+        Some((corners, first, second))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1.0e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let planar_hit = point - self.corner;
+        let alpha = self.w.dot(planar_hit.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hit));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new(point, self.normal, t, self.material.borrow());
+        hit_record.u = alpha;
+        hit_record.v = beta;
+        hit_record.set_face_normal(ray, self.normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // pad so that axis aligned quads do not produce a zero width box
+        let padding = Vec3d::fill(1.0e-4);
+        [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ]
+        .into_iter()
+        .fold(Aabb::EMPTY, |acc, point| {
+            acc.surrounding(Aabb::new(point - padding, point + padding))
+        })
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // samples the solid angle the quad subtends, falling back to its area when that is unreliable
+    fn sample_point(&self, origin: Vec3d, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        let random_triangle = sampler.get_1d();
+        let random_point = sampler.get_2d();
+        let Some((corners, first, second)) = self.spherical_triangles(origin) else {
+            let (alpha, beta) = random_point;
+            return Some(self.corner + self.u * alpha + self.v * beta);
+        };
+
+        let direction = if random_triangle * (first + second) < first {
+            spherical_triangle(corners[0], corners[1], corners[2], random_point)
+        } else {
+            spherical_triangle(corners[0], corners[2], corners[3], random_point)
+        };
+        // only a sliver of a triangle, which is next to never picked, fails to warp; the sample
+        // is dropped, as an area sample would not match the solid angle pdf
+        let direction = direction?;
+
+        // where the sampled direction meets the plane of the quad
+        let denominator = self.normal.dot(direction);
+        if denominator.abs() < 1.0e-12 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(origin)) / denominator;
+        (t > 0.0).then(|| origin + direction * t)
+    }
+
+    // uniform over the subtended solid angle, or the area density converted to solid angle
+    fn pdf_value(&self, origin: Vec3d, direction: Vec3d, time: f64) -> f64 {
+        let Some(hit_record) = self.hit(&Ray::new(origin, direction, time), 1.0e-6, f64::INFINITY)
+        else {
+            return 0.0;
+        };
+
+        if let Some((_, first, second)) = self.spherical_triangles(origin) {
+            return 1.0 / (first + second);
+        }
+
+        let distance_squared = hit_record.t * hit_record.t * direction.length_squared();
+        let cosine = (direction.dot(hit_record.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(u: Vec3d, v: Vec3d) -> Result<Quad, QuadError> {
+        let material = Box::new(DiffuseLight::new(Colour::fill(1.0)));
+        Quad::new(Vec3d::ZERO, u, v, material)
+    }
+
+    #[test]
+    fn rejects_degenerate_sides() {
+        assert!(quad(Vec3d::X, Vec3d::Y).is_ok());
+        assert!(quad(Vec3d::X * 1.0e-3, Vec3d::Y * 1.0e3).is_ok());
+        for (u, v) in [
+            (Vec3d::ZERO, Vec3d::Y),
+            (Vec3d::X, Vec3d::ZERO),
+            (Vec3d::X, Vec3d::X * -2.0),
+            (Vec3d::new(1.0, 1.0, 0.0), Vec3d::new(3.0, 3.0, 0.0)),
+            (Vec3d::fill(f64::NAN), Vec3d::Y),
+        ] {
+            assert!(matches!(quad(u, v), Err(QuadError::Degenerate)));
+        }
+    }
+
+    #[test]
+    fn sampled_points_lie_on_the_quad() {
+        seed_thread_rng(3);
+        let quad = quad(Vec3d::X, Vec3d::Y).unwrap();
+        let mut sampler = IndependentSampler;
+        // near enough for the solid angle warp and far enough for area sampling
+        for origin in [Vec3d::new(0.5, 0.5, 1.0), Vec3d::new(0.5, 0.5, 500.0)] {
+            for _ in 0..100 {
+                let Some(point) = quad.sample_point(origin, 0.0, &mut sampler) else {
+                    continue;
+                };
+                assert!(point.z.abs() < 1.0e-9);
+                assert!((-1.0e-9..=1.0 + 1.0e-9).contains(&point.x));
+                assert!((-1.0e-9..=1.0 + 1.0e-9).contains(&point.y));
+                assert!(quad.pdf_value(origin, point - origin, 0.0) > 0.0);
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::*;
 use std::borrow::Borrow;
+use std::f64::consts::PI;
 
 pub struct Sphere {
     centre: Vec3d,
@@ -18,7 +19,7 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.centre;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(ray.direction);
@@ -38,8 +39,67 @@ impl Hittable for Sphere {
             let point = ray.at(t);
             let normal = (point - self.centre) / self.radius;
             let mut hit_record = HitRecord::new(point, normal, t, self.material.borrow());
+            (hit_record.u, hit_record.v) = sphere_uv(normal);
             hit_record.set_face_normal(ray, normal);
             Some(hit_record)
         }
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vec3d::fill(self.radius.abs());
+        Aabb::new(self.centre - extent, self.centre + extent)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // samples the cone of directions the sphere subtends, or its whole surface from inside
+    fn sample_point(&self, origin: Vec3d, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        let to_centre = self.centre - origin;
+        let distance_squared = to_centre.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            return Some(self.centre + uniform_sphere(sampler.get_2d()) * self.radius.abs());
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let direction = local_to_world(
+            uniform_cone(sampler.get_2d(), cos_theta_max),
+            to_centre.normalise(),
+        );
+
+        // nearest intersection of the sampled direction with the sphere
+        let projection = direction.dot(to_centre);
+        let discriminant = (projection * projection - distance_squared + radius_squared).max(0.0);
+        Some(origin + direction * (projection - discriminant.sqrt()))
+    }
+
+    fn pdf_value(&self, origin: Vec3d, direction: Vec3d, time: f64) -> f64 {
+        let Some(hit_record) = self.hit(&Ray::new(origin, direction, time), 1.0e-6, f64::INFINITY)
+        else {
+            return 0.0;
+        };
+
+        let distance_squared = (self.centre - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            let to_point = hit_record.point - origin;
+            let cosine = to_point.normalise().dot(hit_record.normal).abs();
+            let area = 4.0 * PI * radius_squared;
+            return to_point.length_squared() / (cosine * area);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        uniform_cone_pdf(cos_theta_max)
+    }
+}
+
+// maps a point on the unit sphere to u around the y axis from -x, and v from the south pole
+fn sphere_uv(point: Vec3d) -> (f64, f64) {
+    let theta = (-point.y).clamp(-1.0, 1.0).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
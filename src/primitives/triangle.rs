@@ -0,0 +1,298 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::*;
+
+#[derive(Debug)]
+pub enum MeshError {
+    NormalCount,
+    UvCount,
+    IndexOutOfRange,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NormalCount => write!(f, "mesh needs one normal per position"),
+            Self::UvCount => write!(f, "mesh needs one uv per position"),
+            Self::IndexOutOfRange => write!(f, "mesh index out of range"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+pub struct MeshData {
+    positions: Vec<Vec3d>,
+    normals: Vec<Vec3d>,
+    uvs: Vec<Vec2d>,
+    indices: Vec<[usize; 3]>,
+    material: Box<dyn Material>,
+}
+
+pub struct Triangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: BvhNode,
+    // running total of triangle areas, for picking triangles proportionally to their size
+    area_cdf: Vec<f64>,
+}
+
+impl TriangleMesh {
+    // `normals` and `uvs` are either empty or hold one entry per position
+    pub fn new(
+        positions: Vec<Vec3d>,
+        normals: Vec<Vec3d>,
+        uvs: Vec<Vec2d>,
+        indices: Vec<[usize; 3]>,
+        material: Box<dyn Material>,
+    ) -> Result<Self, MeshError> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(MeshError::NormalCount);
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(MeshError::UvCount);
+        }
+        if indices
+            .iter()
+            .flatten()
+            .any(|&index| index >= positions.len())
+        {
+            return Err(MeshError::IndexOutOfRange);
+        }
+
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+
+        let mut triangles = HittableList::new();
+        for index in 0..mesh.indices.len() {
+            triangles.add(Arc::new(Triangle {
+                mesh: mesh.clone(),
+                index,
+            }));
+        }
+
+        let mut total_area = 0.0;
+        let area_cdf = mesh
+            .indices
+            .iter()
+            .map(|&[i0, i1, i2]| {
+                let p0 = mesh.positions[i0];
+                total_area += (mesh.positions[i1] - p0)
+                    .cross(mesh.positions[i2] - p0)
+                    .length()
+                    / 2.0;
+                total_area
+            })
+            .collect();
+
+        Ok(Self {
+            mesh,
+            bvh: BvhNode::new(triangles),
+            area_cdf,
+        })
+    }
+
+    fn total_area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+
+    fn sample_point(&self, _origin: Vec3d, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        let total_area = self.total_area();
+        if total_area <= 0.0 {
+            return None;
+        }
+
+        let target = sampler.get_1d() * total_area;
+        let index = self
+            .area_cdf
+            .partition_point(|&area| area <= target)
+            .min(self.area_cdf.len() - 1);
+        let [i0, i1, i2] = self.mesh.indices[index];
+
+        // uniform barycentric coordinates
+        let (random_b1, random_b2) = sampler.get_2d();
+        let root = random_b1.sqrt();
+        let b1 = 1.0 - root;
+        let b2 = random_b2 * root;
+        let positions = &self.mesh.positions;
+        Some(positions[i0] * (1.0 - b1 - b2) + positions[i1] * b1 + positions[i2] * b2)
+    }
+
+    fn pdf_value(&self, origin: Vec3d, direction: Vec3d, time: f64) -> f64 {
+        let Some(hit_record) = self.hit(&Ray::new(origin, direction, time), 1.0e-6, f64::INFINITY)
+        else {
+            return 0.0;
+        };
+
+        let distance_squared = hit_record.t * hit_record.t * direction.length_squared();
+        let cosine = (direction.dot(hit_record.geometric_normal) / direction.length()).abs();
+        distance_squared / (cosine * self.total_area())
+    }
+}
+
+impl Triangle {
+    fn vertices(&self) -> [usize; 3] {
+        self.mesh.indices[self.index]
+    }
+}
+
+fn max_dimension(v: Vec3d) -> usize {
+    if v.x > v.y {
+        if v.x > v.z {
+            0
+        } else {
+            2
+        }
+    } else if v.y > v.z {
+        1
+    } else {
+        2
+    }
+}
+
+impl Hittable for Triangle {
+    // watertight ray/triangle intersection (Woop, Benthin and Wald 2013)
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let p1 = self.mesh.positions[i1];
+        let p2 = self.mesh.positions[i2];
+
+        let kz = max_dimension(ray.direction.abs());
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if ray.direction[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        let sx = ray.direction[kx] / ray.direction[kz];
+        let sy = ray.direction[ky] / ray.direction[kz];
+        let sz = 1.0 / ray.direction[kz];
+
+        let a = p0 - ray.origin;
+        let b = p1 - ray.origin;
+        let c = p2 - ray.origin;
+
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        let e0 = cx * by - cy * bx;
+        let e1 = ax * cy - ay * cx;
+        let e2 = bx * ay - by * ax;
+
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+            return None;
+        }
+
+        let determinant = e0 + e1 + e2;
+        if determinant == 0.0 {
+            return None;
+        }
+
+        let scaled_t = e0 * sz * a[kz] + e1 * sz * b[kz] + e2 * sz * c[kz];
+        let t = scaled_t / determinant;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let b0 = e0 / determinant;
+        let b1 = e1 / determinant;
+        let b2 = e2 / determinant;
+
+        let point = p0 * b0 + p1 * b1 + p2 * b2;
+        let geometric_normal = (p1 - p0).cross(p2 - p0).normalise();
+
+        let mut hit_record =
+            HitRecord::new(point, geometric_normal, t, self.mesh.material.borrow());
+        hit_record.set_face_normal(ray, geometric_normal);
+
+        if !self.mesh.normals.is_empty() {
+            let normals = &self.mesh.normals;
+            let shading_normal =
+                (normals[i0] * b0 + normals[i1] * b1 + normals[i2] * b2).normalise_or_zero();
+            if shading_normal != Vec3d::ZERO {
+                hit_record.normal = if shading_normal.dot(hit_record.normal) < 0.0 {
+                    -shading_normal
+                } else {
+                    shading_normal
+                };
+            }
+        }
+
+        if self.mesh.uvs.is_empty() {
+            hit_record.u = b1;
+            hit_record.v = b2;
+        } else {
+            let uvs = &self.mesh.uvs;
+            let uv = uvs[i0] * b0 + uvs[i1] * b1 + uvs[i2] * b2;
+            hit_record.u = uv.x;
+            hit_record.v = uv.y;
+        }
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let p1 = self.mesh.positions[i1];
+        let p2 = self.mesh.positions[i2];
+
+        // pad so that axis aligned triangles do not produce a zero width box
+        let padding = Vec3d::fill(1.0e-4);
+        Aabb::new(p0.min(p1).min(p2) - padding, p0.max(p1).max(p2) + padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_triangle(normals: Vec<Vec3d>) -> TriangleMesh {
+        let positions = vec![Vec3d::ZERO, Vec3d::X, Vec3d::Y];
+        let material = Box::new(DiffuseLight::new(Colour::fill(1.0)));
+        TriangleMesh::new(positions, normals, vec![], vec![[0, 1, 2]], material).unwrap()
+    }
+
+    #[test]
+    fn pdf_ignores_shading_normals() {
+        let flat = unit_triangle(vec![]);
+        let bent = unit_triangle(vec![Vec3d::new(1.0, 1.0, 1.0).normalise(); 3]);
+
+        // straight down onto the triangle from two units away, so distance 2 and cosine 1
+        let origin = Vec3d::new(0.25, 0.25, 2.0);
+        let direction = Vec3d::new(0.0, 0.0, -1.0);
+        let expected = 4.0 / 0.5;
+        assert!((flat.pdf_value(origin, direction, 0.0) - expected).abs() < 1.0e-9);
+        assert!((bent.pdf_value(origin, direction, 0.0) - expected).abs() < 1.0e-9);
+    }
+}
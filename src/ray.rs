@@ -1,9 +1,16 @@
+use std::sync::Arc;
+
 use crate::*;
 
 pub struct HitRecord<'a> {
     pub point: Vec3d,
     pub normal: Vec3d,
+    // the surface's own normal on the same side as `normal`, which may be an interpolated
+    // shading normal instead
+    pub geometric_normal: Vec3d,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub material: &'a dyn Material,
     pub front_face: bool,
 }
@@ -13,7 +20,10 @@ impl<'a> HitRecord<'a> {
         Self {
             point,
             normal,
+            geometric_normal: normal,
             t,
+            u: 0.0,
+            v: 0.0,
             material,
             front_face: false,
         }
@@ -26,15 +36,50 @@ impl<'a> HitRecord<'a> {
         } else {
             -outward_normal
         };
+        self.geometric_normal = self.normal;
     }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    // `hit` for rays traced on behalf of a pixel sample, letting media draw their scattering
+    // distances from its sampler
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        self.hit(ray, t_min, t_max)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // picks a point on the surface as seen from `origin` at `time`, for shapes that can be used
+    // as lights
+    fn sample_point(
+        &self,
+        _origin: Vec3d,
+        _time: f64,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Vec3d> {
+        None
+    }
+
+    // solid angle density of `sample_point` producing a point along `direction` from `origin` at
+    // `time`
+    fn pdf_value(&self, _origin: Vec3d, _direction: Vec3d, _time: f64) -> f64 {
+        0.0
+    }
 }
 
 pub struct HittableList {
-    objects: Vec<Box<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
@@ -42,59 +87,120 @@ impl HittableList {
         Self { objects: vec![] }
     }
 
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
     }
+
+    pub fn into_objects(self) -> Vec<Arc<dyn Hittable>> {
+        self.objects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn emissive_objects(&self) -> HittableList {
+        Self {
+            objects: self
+                .objects
+                .iter()
+                .filter(|object| object.is_emissive())
+                .cloned()
+                .collect(),
+        }
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut hit_result: Option<HitRecord> = None;
-        let mut closest_so_far = t_max;
-
-        for object in &self.objects {
-            if let Some(hit_record) = object.hit(ray, t_min, closest_so_far) {
-                closest_so_far = hit_record.t;
-                hit_result = Some(hit_record);
-            }
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        closest_hit(&self.objects, ray, t_min, t_max, None)
+    }
+
+    fn hit_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        closest_hit(&self.objects, ray, t_min, t_max, Some(sampler))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::EMPTY, |acc, object| {
+            acc.surrounding(object.bounding_box())
+        })
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.objects.iter().any(|object| object.is_emissive())
+    }
+
+    // picks one object uniformly, so the density is the average over all objects
+    fn sample_point(&self, origin: Vec3d, time: f64, sampler: &mut dyn Sampler) -> Option<Vec3d> {
+        if self.objects.is_empty() {
+            return None;
         }
+        let index =
+            ((sampler.get_1d() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].sample_point(origin, time, sampler)
+    }
+
+    fn pdf_value(&self, origin: Vec3d, direction: Vec3d, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, time))
+            .sum();
+        total / self.objects.len() as f64
+    }
+}
 
-        hit_result
+// the nearest hit among `objects`, passing `sampler` on when there is one
+pub fn closest_hit<'a>(
+    objects: &'a [Arc<dyn Hittable>],
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    mut sampler: Option<&mut (dyn Sampler + '_)>,
+) -> Option<HitRecord<'a>> {
+    let mut hit_result: Option<HitRecord> = None;
+    let mut closest_so_far = t_max;
+
+    for object in objects {
+        let hit_record = match sampler.as_deref_mut() {
+            Some(sampler) => object.hit_sampled(ray, t_min, closest_so_far, sampler),
+            None => object.hit(ray, t_min, closest_so_far),
+        };
+        if let Some(hit_record) = hit_record {
+            closest_so_far = hit_record.t;
+            hit_result = Some(hit_record);
+        }
     }
+
+    hit_result
 }
 
 pub struct Ray {
     pub origin: Vec3d,
     pub direction: Vec3d,
+    // the moment within the shutter interval the ray was sent, which moving objects are placed at
+    pub time: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Vector<f64, 3>, direction: Vector<f64, 3>) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Vector<f64, 3>, direction: Vector<f64, 3>, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f64) -> Vector<f64, 3> {
         self.origin + self.direction * t
     }
-
-    pub fn ray_colour<T: Hittable>(&self, hittable: &T, depth: u32) -> Colour {
-        if let Some(hit_result) = hittable.hit(self, 1.0e-6, f64::INFINITY) {
-            return if depth > 0 {
-                if let Some(scatter_record) = hit_result.material.scatter(self, &hit_result) {
-                    Colour::multiply_colour(
-                        scatter_record.attenuation,
-                        scatter_record.scattered.ray_colour(hittable, depth - 1),
-                    )
-                } else {
-                    Colour::new(0.0, 0.0, 0.0)
-                }
-            } else {
-                Colour::new(0.0, 0.0, 0.0)
-            };
-        }
-
-        let unit_direction = self.direction.normalise();
-        let t = 0.5 * (unit_direction.y + 1.0);
-        Colour::new(1.0, 1.0, 1.0) * (1.0 - t) + Colour::new(0.5, 0.7, 1.0) * t
-    }
 }
@@ -0,0 +1,249 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use crate::*;
+
+pub struct Tile {
+    pub x_start: u32,
+    pub y_start: u32,
+    pub x_end: u32,
+    pub y_end: u32,
+}
+
+pub fn split_into_tiles(width: u32, height: u32, tile_size: u32) -> Vec<Tile> {
+    let mut tiles = vec![];
+
+    for y_start in (0..height).step_by(tile_size as usize) {
+        for x_start in (0..width).step_by(tile_size as usize) {
+            tiles.push(Tile {
+                x_start,
+                y_start,
+                x_end: x_start.saturating_add(tile_size).min(width),
+                y_end: y_start.saturating_add(tile_size).min(height),
+            });
+        }
+    }
+
+    tiles
+}
+
+#[derive(Clone)]
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub russian_roulette_depth: u32,
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    // multiplies the averaged radiance before it is written out
+    pub exposure: f64,
+}
+
+impl RenderSettings {
+    pub fn new(
+        image_width: u32,
+        aspect_ratio: f64,
+        samples_per_pixel: u32,
+        max_depth: u32,
+    ) -> Self {
+        Self {
+            image_width,
+            image_height: (image_width as f64 / aspect_ratio) as u32,
+            samples_per_pixel,
+            max_depth,
+            russian_roulette_depth: RUSSIAN_ROULETTE_DEPTH,
+            seed: SEED,
+            sampler: SamplerKind::Independent,
+            exposure: 1.0,
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.image_width < 2 || self.image_height < 2 {
+            return Err(format!(
+                "image must be at least 2x2 pixels, got {}x{}",
+                self.image_width, self.image_height
+            ));
+        }
+        let pixel_count = (self.image_width as usize).checked_mul(self.image_height as usize);
+        if pixel_count.is_none_or(|count| count > MAX_IMAGE_PIXELS) {
+            return Err(format!(
+                "image of {}x{} pixels is larger than the limit of {MAX_IMAGE_PIXELS} pixels",
+                self.image_width, self.image_height
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err("samples per pixel must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self::new(IMAGE_WIDTH, ASPECT_RATIO, SAMPLES_PER_PIXEL, MAX_DEPTH)
+    }
+}
+
+// renders the image in `tile_size` squares on `thread_count` workers and returns the pixels top
+// row first; neither changes the result, as every pixel seeds its own samples
+pub fn render<T: Hittable>(
+    camera: &dyn Camera,
+    world: &T,
+    lights: &Lights,
+    environment: &dyn Environment,
+    settings: &RenderSettings,
+    thread_count: usize,
+    tile_size: u32,
+) -> Vec<Colour> {
+    let tiles = split_into_tiles(settings.image_width, settings.image_height, tile_size);
+    let next_tile = AtomicUsize::new(0);
+    let pixel_count = settings.image_width as usize * settings.image_height as usize;
+    let mut framebuffer = vec![Colour::new(0.0, 0.0, 0.0); pixel_count];
+
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..thread_count.max(1) {
+            let sender = sender.clone();
+            let tiles = &tiles;
+            let next_tile = &next_tile;
+            scope.spawn(move || {
+                while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                    let pixels = render_tile(tile, camera, world, lights, environment, settings);
+                    if sender.send((tile, pixels)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (finished, (tile, pixels)) in receiver.iter().enumerate() {
+            let tile_width = (tile.x_end - tile.x_start) as usize;
+            for (row, y) in (tile.y_start..tile.y_end).enumerate() {
+                let offset = y as usize * settings.image_width as usize + tile.x_start as usize;
+                framebuffer[offset..offset + tile_width]
+                    .copy_from_slice(&pixels[row * tile_width..(row + 1) * tile_width]);
+            }
+            println!("{}%", ((finished + 1) * 100) / tiles.len());
+        }
+    });
+
+    framebuffer
+}
+
+fn render_tile<T: Hittable>(
+    tile: &Tile,
+    camera: &dyn Camera,
+    world: &T,
+    lights: &Lights,
+    environment: &dyn Environment,
+    settings: &RenderSettings,
+) -> Vec<Colour> {
+    let integrator = PathTracer::new(settings.max_depth, settings.russian_roulette_depth);
+    let mut sampler = settings
+        .sampler
+        .build(settings.samples_per_pixel, settings.seed);
+
+    let mut pixels = Vec::with_capacity(
+        (tile.x_end - tile.x_start) as usize * (tile.y_end - tile.y_start) as usize,
+    );
+
+    for pixel_y in tile.y_start..tile.y_end {
+        for pixel_x in tile.x_start..tile.x_end {
+            // flip image
+            let x = pixel_x as f64;
+            let y = (settings.image_height - pixel_y) as f64;
+
+            let mut pixel_colour: Colour = Vec3d::new(0.0, 0.0, 0.0);
+
+            let pixel_seed = mix_seed(
+                settings.seed,
+                pixel_y as u64 * settings.image_width as u64 + pixel_x as u64,
+            );
+
+            for sample_index in 0..settings.samples_per_pixel {
+                // every sample gets its own random stream, so neither threads nor tiling change it
+                seed_thread_rng(mix_seed(pixel_seed, sample_index as u64));
+                sampler.start_pixel_sample(pixel_x, pixel_y, sample_index);
+
+                let (jitter_x, jitter_y) = sampler.get_2d();
+                let u =
+                    (x + (2.0 * jitter_x - 1.0) * SAMPLE_RANGE) / (settings.image_width - 1) as f64;
+                let v = (y + (2.0 * jitter_y - 1.0) * SAMPLE_RANGE)
+                    / (settings.image_height - 1) as f64;
+                if let Some(ray) = camera.get_ray(u, v, sampler.as_mut()) {
+                    pixel_colour +=
+                        integrator.radiance(ray, world, lights, environment, sampler.as_mut());
+                }
+            }
+
+            pixels.push(pixel_colour);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+
+    use super::*;
+
+    fn render_scene(scene: Scene, thread_count: usize, tile_size: u32) -> Vec<[u64; 3]> {
+        let mut settings = scene.settings;
+        settings.image_width = 36;
+        settings.image_height = 24;
+        settings.samples_per_pixel = 4;
+        settings.max_depth = 8;
+
+        let camera = scene.camera.build(settings.aspect_ratio(), 0.0);
+        let lights = Lights::new(scene.world.emissive_objects(), scene.lights);
+        let world = BvhNode::new(scene.world);
+        let pixels = render(
+            camera.as_ref(),
+            &world,
+            &lights,
+            scene.environment.as_ref(),
+            &settings,
+            thread_count,
+            tile_size,
+        );
+
+        pixels
+            .iter()
+            .map(|pixel| [pixel.x.to_bits(), pixel.y.to_bits(), pixel.z.to_bits()])
+            .collect()
+    }
+
+    fn with_sampler(mut scene: Scene, sampler: SamplerKind) -> Scene {
+        scene.settings.sampler = sampler;
+        scene
+    }
+
+    #[test]
+    fn threads_and_tiles_do_not_change_the_image() {
+        for &sampler in SamplerKind::value_variants() {
+            let single = render_scene(with_sampler(cornell_box().unwrap(), sampler), 1, TILE_SIZE);
+            let threaded = render_scene(with_sampler(cornell_box().unwrap(), sampler), 4, 5);
+            assert!(single == threaded);
+        }
+    }
+
+    #[test]
+    fn random_world_is_the_same_for_the_same_seed() {
+        let first = render_scene(random_world(3), 2, TILE_SIZE);
+        let second = render_scene(random_world(3), 3, 7);
+        assert!(first == second);
+
+        let other = render_scene(random_world(4), 2, TILE_SIZE);
+        assert!(first != other);
+    }
+}
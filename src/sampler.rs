@@ -0,0 +1,437 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::*;
+
+// hands out the random numbers for one pixel sample at a time, one dimension after another
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    /// Uniform random numbers with no structure
+    Independent,
+    /// One jittered sample in each stratum of every dimension
+    Stratified,
+    /// Halton sequence, Owen-scrambled differently in every pixel
+    Halton,
+    /// Owen-scrambled Sobol points, best with a power of two samples per pixel
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn build(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler),
+            Self::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+// where a sampler is up to, and the hash that makes each pixel and dimension different
+struct SampleState {
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl SampleState {
+    const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.pixel_hash = mix_seed(self.seed, ((y as u64) << 32) | x as u64);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    // a hash for the next dimension, which is then used up
+    fn next_dimension(&mut self) -> u64 {
+        let hash = mix_seed(self.pixel_hash, self.dimension);
+        self.dimension += 1;
+        hash
+    }
+}
+
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, _x: u32, _y: u32, _sample_index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        gen_random()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (gen_random(), gen_random())
+    }
+}
+
+// splits each dimension into as many strata as there are samples, visiting them in a different
+// order per pixel and dimension; 2D samples use a grid as close to square as the count allows
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    columns: u32,
+    rows: u32,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let columns = (1..=(samples_per_pixel as f64).sqrt() as u32)
+            .rev()
+            .find(|columns| samples_per_pixel.is_multiple_of(*columns))
+            .unwrap_or(1);
+        Self {
+            samples_per_pixel,
+            columns,
+            rows: samples_per_pixel / columns,
+            state: SampleState::new(seed),
+        }
+    }
+
+    fn stratum(&mut self) -> u32 {
+        let hash = self.state.next_dimension();
+        permutation_element(
+            self.state.sample_index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            hash as u32,
+        )
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        (self.stratum() as f64 + gen_random()) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum();
+        (
+            ((stratum % self.columns) as f64 + gen_random()) / self.columns as f64,
+            ((stratum / self.columns) as f64 + gen_random()) / self.rows as f64,
+        )
+    }
+}
+
+// the radical inverse of the sample index in a different prime base for every dimension, with the
+// digits Owen-scrambled per pixel so neighbouring pixels do not share a pattern
+pub struct HaltonSampler {
+    primes: &'static [u32],
+    state: SampleState,
+}
+
+// dimensions past this many fall back to independent random numbers
+const HALTON_DIMENSIONS: usize = 256;
+
+// the first `HALTON_DIMENSIONS` primes, found once and shared by every tile's sampler
+static HALTON_PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        let primes = HALTON_PRIMES.get_or_init(|| {
+            let mut primes = Vec::with_capacity(HALTON_DIMENSIONS);
+            let mut candidate = 2;
+            while primes.len() < HALTON_DIMENSIONS {
+                if primes.iter().all(|prime| candidate % prime != 0) {
+                    primes.push(candidate);
+                }
+                candidate += 1;
+            }
+            primes
+        });
+        Self {
+            primes,
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.dimension as usize;
+        let hash = self.state.next_dimension();
+        let Some(&base) = self.primes.get(dimension) else {
+            return gen_random();
+        };
+
+        scrambled_radical_inverse(base, self.state.sample_index, hash)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// padded Sobol points: every 1D and 2D request uses the first Sobol dimensions with the sample
+// order shuffled and the points Owen-scrambled independently per pixel and dimension
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            state: SampleState::new(seed),
+        }
+    }
+
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        permutation_element(
+            self.state.sample_index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            hash as u32,
+        )
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_dimension();
+        let index = self.shuffled_index(hash);
+        to_unit_float(owen_scramble(index.reverse_bits(), (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_dimension();
+        let index = self.shuffled_index(hash);
+        let scramble = mix_seed(hash, 0);
+        (
+            to_unit_float(owen_scramble(index.reverse_bits(), scramble as u32)),
+            to_unit_float(owen_scramble(
+                sobol_second_dimension(index),
+                (scramble >> 32) as u32,
+            )),
+        )
+    }
+}
+
+// reflects the base `base` digits of `index` about the point, permuting each digit by a hash of the
+// digits before it; carries on past the last digit so the trailing zeros are scrambled too
+fn scrambled_radical_inverse(base: u32, mut index: u32, hash: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut digit_weight = inverse_base;
+    let mut previous_digits: u64 = 0;
+    let mut value = 0.0;
+    while digit_weight > 1.0e-10 {
+        let digit = index % base;
+        index /= base;
+
+        let digit_hash = mix_seed(hash, previous_digits);
+        value += permutation_element(digit, base, digit_hash as u32) as f64 * digit_weight;
+
+        previous_digits = previous_digits
+            .wrapping_mul(base as u64)
+            .wrapping_add(digit as u64);
+        digit_weight *= inverse_base;
+    }
+    value.min(1.0 - f64::EPSILON)
+}
+
+// the second Sobol dimension, generated by the polynomial x + 1
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+// hash based Owen scrambling of the bits of `value`, as in Laine and Karras 2011
+fn owen_scramble(value: u32, seed: u32) -> u32 {
+    let mut value = value.reverse_bits();
+    value ^= value.wrapping_mul(0x3d20_adea);
+    value = value.wrapping_add(seed);
+    value = value.wrapping_mul((seed >> 16) | 1);
+    value ^= value.wrapping_mul(0x0552_6c56);
+    value ^= value.wrapping_mul(0x53a2_2864);
+    value.reverse_bits()
+}
+
+// element `index` of a random permutation of 0..count chosen by `seed`, without storing the
+// permutation (Kensler 2013)
+fn permutation_element(index: u32, count: u32, seed: u32) -> u32 {
+    let mut mask = count.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    let mut element = index;
+    loop {
+        element ^= seed;
+        element = element.wrapping_mul(0xe170_893d);
+        element ^= seed >> 16;
+        element ^= (element & mask) >> 4;
+        element ^= seed >> 8;
+        element = element.wrapping_mul(0x0929_eb3f);
+        element ^= seed >> 23;
+        element ^= (element & mask) >> 1;
+        element = element.wrapping_mul(1 | seed >> 27);
+        element = element.wrapping_mul(0x6935_fa69);
+        element ^= (element & mask) >> 11;
+        element = element.wrapping_mul(0x74dc_b303);
+        element ^= (element & mask) >> 2;
+        element = element.wrapping_mul(0x9e50_1cc3);
+        element ^= (element & mask) >> 2;
+        element = element.wrapping_mul(0xc860_a3df);
+        element &= mask;
+        element ^= element >> 5;
+        if element < count {
+            break;
+        }
+    }
+    element.wrapping_add(seed) % count
+}
+
+fn to_unit_float(value: u32) -> f64 {
+    value as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draws `dimensions` alternately 1D and 2D values for every sample of a pixel
+    fn pixel_samples(
+        sampler: &mut dyn Sampler,
+        (x, y): (u32, u32),
+        samples_per_pixel: u32,
+        dimensions: usize,
+    ) -> Vec<Vec<(f64, f64)>> {
+        (0..samples_per_pixel)
+            .map(|sample_index| {
+                sampler.start_pixel_sample(x, y, sample_index);
+                (0..dimensions)
+                    .map(|dimension| {
+                        if dimension % 2 == 0 {
+                            (sampler.get_1d(), 0.0)
+                        } else {
+                            sampler.get_2d()
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn samples_lie_in_the_unit_interval() {
+        seed_thread_rng(8);
+        for &kind in SamplerKind::value_variants() {
+            for samples_per_pixel in [1, 12, 16] {
+                let mut sampler = kind.build(samples_per_pixel, 5);
+                for pixel in [(0, 0), (7, 3), (1023, 767)] {
+                    // past the Halton table, where it falls back to random numbers
+                    let samples =
+                        pixel_samples(sampler.as_mut(), pixel, samples_per_pixel, 2 * 300);
+                    for (first, second) in samples.into_iter().flatten() {
+                        assert!((0.0..1.0).contains(&first) && (0.0..1.0).contains(&second));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_in_each_stratum() {
+        seed_thread_rng(9);
+        // 12 makes a 3 by 4 grid, 7 a single column
+        for (samples_per_pixel, columns, rows) in [(12, 3, 4), (16, 4, 4), (7, 1, 7)] {
+            let mut sampler = StratifiedSampler::new(samples_per_pixel, 5);
+            for pixel in [(0, 0), (5, 9)] {
+                let samples = pixel_samples(&mut sampler, pixel, samples_per_pixel, 6);
+                for dimension in 0..6 {
+                    let mut strata: Vec<usize> = samples
+                        .iter()
+                        .map(|sample| {
+                            let (first, second) = sample[dimension];
+                            if dimension % 2 == 0 {
+                                (first * samples_per_pixel as f64) as usize
+                            } else {
+                                (second * rows as f64) as usize * columns
+                                    + (first * columns as f64) as usize
+                            }
+                        })
+                        .collect();
+                    strata.sort_unstable();
+                    assert_eq!(strata, (0..samples_per_pixel as usize).collect::<Vec<_>>());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_element_is_a_bijection() {
+        for count in [1, 2, 3, 7, 12, 100, 1000, 1025] {
+            for seed in [0, 1, 0xdead_beef] {
+                let mut elements: Vec<u32> = (0..count)
+                    .map(|index| permutation_element(index, count, seed))
+                    .collect();
+                elements.sort_unstable();
+                assert_eq!(elements, (0..count).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_power_of_two_points_are_0_2_stratified() {
+        for log_count in [2, 4, 6] {
+            let count = 1u32 << log_count;
+            let mut sampler = SobolSampler::new(count, 5);
+            for pixel in [(0, 0), (3, 11)] {
+                let samples = pixel_samples(&mut sampler, pixel, count, 6);
+                for dimension in [1, 3, 5] {
+                    // every split of the square into `count` boxes of 2^a by 2^b holds one point
+                    for log_columns in 0..=log_count {
+                        let columns = 1usize << log_columns;
+                        let rows = count as usize / columns;
+                        let mut occupied = vec![false; count as usize];
+                        for sample in &samples {
+                            let (x, y) = sample[dimension];
+                            let cell = (y * rows as f64) as usize * columns
+                                + (x * columns as f64) as usize;
+                            assert!(!occupied[cell], "{columns} by {rows} cell {cell} twice");
+                            occupied[cell] = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
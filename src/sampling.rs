@@ -0,0 +1,253 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::*;
+
+// warps from uniform numbers in [0, 1)^2 to the shapes light is sampled over; directions in a
+// local frame have z along the axis they are centred on
+
+pub fn uniform_sphere(u: (f64, f64)) -> Vec3d {
+    let z = 1.0 - 2.0 * u.0;
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3d::new(radius * phi.cos(), radius * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+// Shirley and Chiu's mapping, which keeps strata in the square as compact regions on the disk
+pub fn concentric_disk(u: (f64, f64)) -> (f64, f64) {
+    let x = 2.0 * u.0 - 1.0;
+    let y = 2.0 * u.1 - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    (radius * theta.cos(), radius * theta.sin())
+}
+
+// projects the disk up onto the hemisphere, giving a density proportional to the cosine
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vec3d {
+    let (x, y) = concentric_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    Vec3d::new(x, y, z)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+// directions within `cos_theta_max` of the axis, uniform over solid angle
+pub fn uniform_cone(u: (f64, f64), cos_theta_max: f64) -> Vec3d {
+    let cos_theta = 1.0 + u.0 * (cos_theta_max - 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3d::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f64) -> f64 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+// solid angle of the triangle the unit vectors `a`, `b` and `c` span (Van Oosterom and Strackee)
+pub fn spherical_triangle_area(a: Vec3d, b: Vec3d, c: Vec3d) -> f64 {
+    let numerator = a.dot(b.cross(c)).abs();
+    let denominator = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+    (2.0 * numerator.atan2(denominator)).abs()
+}
+
+// a direction uniform over the solid angle of the spherical triangle `a`, `b`, `c` (Arvo 1995);
+// the density is one over `spherical_triangle_area`
+pub fn spherical_triangle(a: Vec3d, b: Vec3d, c: Vec3d, u: (f64, f64)) -> Option<Vec3d> {
+    let normal_ab = a.cross(b);
+    let normal_bc = b.cross(c);
+    let normal_ca = c.cross(a);
+    if normal_ab.length_squared() == 0.0
+        || normal_bc.length_squared() == 0.0
+        || normal_ca.length_squared() == 0.0
+    {
+        return None;
+    }
+    let normal_ab = normal_ab.normalise();
+    let normal_bc = normal_bc.normalise();
+    let normal_ca = normal_ca.normalise();
+
+    let alpha = angle_between(normal_ab, -normal_ca);
+    let beta = angle_between(normal_bc, -normal_ab);
+    let gamma = angle_between(normal_ca, -normal_bc);
+
+    // pick the area of the sub-triangle whose third vertex lies along the edge from a to c
+    let area_pi = alpha + beta + gamma;
+    let sub_area_pi = PI + u.0 * (area_pi - PI);
+    if area_pi - PI <= 0.0 {
+        return None;
+    }
+
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+    let sin_phi = sub_area_pi.sin() * cos_alpha - sub_area_pi.cos() * sin_alpha;
+    let cos_phi = sub_area_pi.cos() * cos_alpha + sub_area_pi.sin() * sin_alpha;
+
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_b = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+        .clamp(-1.0, 1.0);
+    let sin_b = (1.0 - cos_b * cos_b).max(0.0).sqrt();
+    let c_prime = a * cos_b + perpendicular_part(c, a) * sin_b;
+
+    // then a point along the arc from b to that vertex
+    let cos_theta = 1.0 - u.1 * (1.0 - c_prime.dot(b));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Some(b * cos_theta + perpendicular_part(c_prime, b) * sin_theta)
+}
+
+// turns `local` from the frame around the unit vector `w` into world space
+pub fn local_to_world(local: Vec3d, w: Vec3d) -> Vec3d {
+    let (u, v) = orthonormal_basis(w);
+    u * local.x + v * local.y + w * local.z
+}
+
+// numerically stable angle between two unit vectors
+fn angle_between(a: Vec3d, b: Vec3d) -> f64 {
+    if a.dot(b) < 0.0 {
+        PI - 2.0 * ((a + b).length() / 2.0).clamp(-1.0, 1.0).asin()
+    } else {
+        2.0 * ((b - a).length() / 2.0).clamp(-1.0, 1.0).asin()
+    }
+}
+
+// the unit vector along the part of `v` perpendicular to the unit vector `w`
+fn perpendicular_part(v: Vec3d, w: Vec3d) -> Vec3d {
+    (v - w * v.dot(w)).normalise_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 200_000;
+    const TOLERANCE: f64 = 0.02;
+
+    fn random_pair() -> (f64, f64) {
+        (gen_random(), gen_random())
+    }
+
+    fn mean(mut value: impl FnMut() -> f64) -> f64 {
+        (0..SAMPLES).map(|_| value()).sum::<f64>() / SAMPLES as f64
+    }
+
+    // integrates `pdf` over the whole sphere of directions
+    fn integrate_over_sphere(pdf: impl Fn(Vec3d) -> f64) -> f64 {
+        mean(|| pdf(uniform_sphere(random_pair())) / uniform_sphere_pdf())
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < TOLERANCE,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn uniform_sphere_covers_the_sphere_evenly() {
+        seed_thread_rng(1);
+        assert_close(integrate_over_sphere(|_| uniform_sphere_pdf()), 1.0);
+        assert_close(mean(|| uniform_sphere(random_pair()).z), 0.0);
+        assert_close(mean(|| uniform_sphere(random_pair()).z.powi(2)), 1.0 / 3.0);
+        for _ in 0..1000 {
+            assert_close(uniform_sphere(random_pair()).length(), 1.0);
+        }
+    }
+
+    #[test]
+    fn concentric_disk_covers_the_disk_evenly() {
+        seed_thread_rng(2);
+        let radius_squared = || {
+            let (x, y) = concentric_disk(random_pair());
+            x * x + y * y
+        };
+        // half the area of a unit disk lies within radius squared of a half
+        assert_close(mean(radius_squared), 0.5);
+        for _ in 0..1000 {
+            assert!(radius_squared() <= 1.0 + 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_matches_its_pdf() {
+        seed_thread_rng(3);
+        assert_close(
+            integrate_over_sphere(|direction| cosine_hemisphere_pdf(direction.z)),
+            1.0,
+        );
+        assert_close(mean(|| cosine_hemisphere(random_pair()).z), 2.0 / 3.0);
+        for _ in 0..1000 {
+            let direction = cosine_hemisphere(random_pair());
+            assert!(direction.z >= 0.0);
+            assert_close(direction.length(), 1.0);
+        }
+    }
+
+    #[test]
+    fn uniform_cone_matches_its_pdf() {
+        seed_thread_rng(4);
+        let cos_theta_max = 0.5;
+        let pdf = |direction: Vec3d| {
+            if direction.z >= cos_theta_max {
+                uniform_cone_pdf(cos_theta_max)
+            } else {
+                0.0
+            }
+        };
+        assert_close(integrate_over_sphere(pdf), 1.0);
+        assert_close(
+            mean(|| uniform_cone(random_pair(), cos_theta_max).z),
+            (1.0 + cos_theta_max) / 2.0,
+        );
+        for _ in 0..1000 {
+            assert!(uniform_cone(random_pair(), cos_theta_max).z >= cos_theta_max - 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn spherical_triangle_matches_its_area() {
+        seed_thread_rng(5);
+        let a = Vec3d::new(1.0, 0.2, 0.1).normalise();
+        let b = Vec3d::new(-0.3, 1.0, 0.4).normalise();
+        let c = Vec3d::new(0.2, -0.1, 1.0).normalise();
+        let area = spherical_triangle_area(a, b, c);
+        let normals = [a.cross(b), b.cross(c), c.cross(a)];
+        let inside = |direction: Vec3d| normals.iter().all(|normal| normal.dot(direction) >= 0.0);
+
+        assert_close(
+            integrate_over_sphere(|direction| if inside(direction) { 1.0 / area } else { 0.0 }),
+            1.0,
+        );
+        for _ in 0..1000 {
+            let direction = spherical_triangle(a, b, c, random_pair()).unwrap();
+            assert!(normals
+                .iter()
+                .all(|normal| normal.dot(direction) >= -1.0e-9));
+        }
+    }
+
+    #[test]
+    fn spherical_triangle_is_uniform_over_the_octant() {
+        seed_thread_rng(6);
+        let octant = || spherical_triangle(Vec3d::X, Vec3d::Y, Vec3d::Z, random_pair()).unwrap();
+        assert_close(
+            spherical_triangle_area(Vec3d::X, Vec3d::Y, Vec3d::Z),
+            FRAC_PI_2,
+        );
+        // a uniform direction in the octant has every component averaging a half
+        assert_close(mean(|| octant().x), 0.5);
+        assert_close(mean(|| octant().y), 0.5);
+        assert_close(mean(|| octant().z), 0.5);
+    }
+}
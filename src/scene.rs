@@ -0,0 +1,1044 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::*;
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: CameraSettings,
+    pub lights: Vec<Box<dyn Light>>,
+    pub environment: Box<dyn Environment>,
+    pub settings: RenderSettings,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+    Obj(ObjError),
+    Image(ImageError),
+    Grid(GridError),
+    Mesh(MeshError),
+    Quad(QuadError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Invalid { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::Obj(error) => error.fmt(f),
+            Self::Image(error) => error.fmt(f),
+            Self::Grid(error) => error.fmt(f),
+            Self::Mesh(error) => error.fmt(f),
+            Self::Quad(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Invalid { .. } => None,
+            Self::Obj(error) => Some(error),
+            Self::Image(error) => Some(error),
+            Self::Grid(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::Quad(error) => Some(error),
+        }
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(error: ObjError) -> Self {
+        Self::Obj(error)
+    }
+}
+
+impl From<ImageError> for SceneError {
+    fn from(error: ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+impl From<GridError> for SceneError {
+    fn from(error: GridError) -> Self {
+        Self::Grid(error)
+    }
+}
+
+impl From<MeshError> for SceneError {
+    fn from(error: MeshError) -> Self {
+        Self::Mesh(error)
+    }
+}
+
+impl From<QuadError> for SceneError {
+    fn from(error: QuadError) -> Self {
+        Self::Quad(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    render: RenderSection,
+    camera: CameraSection,
+    #[serde(default)]
+    environment: EnvironmentDescription,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    lights: Vec<LightDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RenderSection {
+    width: u32,
+    aspect_ratio: f64,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    russian_roulette_depth: u32,
+    seed: u64,
+    sampler: SamplerKind,
+}
+
+impl Default for RenderSection {
+    fn default() -> Self {
+        Self {
+            width: IMAGE_WIDTH,
+            aspect_ratio: ASPECT_RATIO,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            max_depth: MAX_DEPTH,
+            russian_roulette_depth: RUSSIAN_ROULETTE_DEPTH,
+            seed: SEED,
+            sampler: SamplerKind::Independent,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraSection {
+    look_from: Option<[f64; 3]>,
+    look_at: Option<[f64; 3]>,
+    #[serde(default = "default_up")]
+    up: [f64; 3],
+    #[serde(default)]
+    projection: Projection,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    #[serde(default)]
+    aperture_shape: ApertureDescription,
+    focus_distance: Option<f64>,
+    #[serde(default)]
+    shutter_open: f64,
+    shutter_close: Option<f64>,
+    // sets the field of view, aperture, shutter and exposure from real camera controls instead
+    physical: Option<PhysicalCamera>,
+    // animates the camera in place of `look_from` and `look_at`
+    #[serde(default)]
+    keyframes: Vec<KeyframeDescription>,
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+// the camera looks either at `look_at` with the camera's up vector, or along an `orientation`
+// quaternion given as [x, y, z, w]; field of view and focus distance default to the camera's
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDescription {
+    frame: f64,
+    look_from: [f64; 3],
+    look_at: Option<[f64; 3]>,
+    orientation: Option<[f64; 4]>,
+    vfov: Option<f64>,
+    focus_distance: Option<f64>,
+}
+
+impl KeyframeDescription {
+    fn to_keyframe(
+        &self,
+        up_vector: Vec3d,
+        vfov: f64,
+        focus_distance: Option<f64>,
+    ) -> Result<CameraKeyframe, String> {
+        let position = vec3(self.look_from);
+        let (orientation, target_distance) = match (self.look_at, self.orientation) {
+            (Some(look_at), None) => {
+                let forward = vec3(look_at) - position;
+                if let Some(message) = view_error(position, vec3(look_at), up_vector) {
+                    return Err(format!("keyframe at frame {} {message}", self.frame));
+                }
+                (
+                    QuatDouble::from_forward_up(forward.normalise(), up_vector),
+                    Some(forward.length()),
+                )
+            }
+            (None, Some([x, y, z, w])) => {
+                let orientation = QuatDouble::from_xyzw(x, y, z, w);
+                if orientation.data.length_squared() == 0.0 {
+                    return Err(format!(
+                        "keyframe at frame {} has a zero orientation",
+                        self.frame
+                    ));
+                }
+                (orientation.normalise(), None)
+            }
+            _ => {
+                return Err(format!(
+                    "keyframe at frame {} needs exactly one of `look_at` and `orientation`",
+                    self.frame
+                ))
+            }
+        };
+
+        let focus_distance = self
+            .focus_distance
+            .or(focus_distance)
+            .or(target_distance)
+            .ok_or_else(|| format!("keyframe at frame {} needs a focus distance", self.frame))?;
+
+        Ok(CameraKeyframe {
+            frame: self.frame,
+            position,
+            orientation,
+            vfov: self.vfov.unwrap_or(vfov),
+            focus_distance,
+        })
+    }
+}
+
+const DEFAULT_VFOV: f64 = 40.0;
+
+// polygon rotation in degrees; a mask is a greyscale image of the opening
+#[derive(Deserialize, Default)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ApertureDescription {
+    #[default]
+    Circle,
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation: f64,
+    },
+    Mask {
+        path: PathBuf,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDescription {
+    Constant {
+        colour: [f64; 3],
+    },
+    Gradient {
+        horizon: [f64; 3],
+        zenith: [f64; 3],
+    },
+    Equirectangular {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    // sun position in degrees, azimuth turning from +z towards +x
+    Sky {
+        elevation: f64,
+        #[serde(default)]
+        azimuth: f64,
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+impl Default for EnvironmentDescription {
+    fn default() -> Self {
+        Self::Gradient {
+            horizon: [1.0, 1.0, 1.0],
+            zenith: [0.5, 0.7, 1.0],
+        }
+    }
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+// a texture is either a plain `[r, g, b]` colour or a table with a `type`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureDescription {
+    Colour([f64; 3]),
+    Texture(TextureKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureKind {
+    Checker {
+        scale: f64,
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>,
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default = "default_wrap")]
+        wrap: WrapMode,
+    },
+}
+
+fn default_wrap() -> WrapMode {
+    WrapMode::Repeat
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: TextureDescription,
+    },
+    Metal {
+        albedo: TextureDescription,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+    Isotropic {
+        albedo: [f64; 3],
+    },
+    HenyeyGreenstein {
+        albedo: [f64; 3],
+        g: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        centre: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Quad {
+        corner: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    TriangleMesh {
+        positions: Vec<[f64; 3]>,
+        indices: Vec<[usize; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        material: String,
+    },
+    Obj {
+        path: PathBuf,
+    },
+    // fills a closed convex boundary, scattering with the boundary's material, which must be a
+    // phase function
+    ConstantMedium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+    },
+    // moves the wrapped object by `displacement` between `time0` and `time1`
+    Moving {
+        object: Box<ObjectDescription>,
+        displacement: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+    },
+    // a density grid stretched over the box from `minimum` to `maximum`, scattering with a phase
+    // function material
+    GridMedium {
+        path: PathBuf,
+        minimum: [f64; 3],
+        maximum: [f64; 3],
+        #[serde(default = "default_density")]
+        density: f64,
+        material: String,
+    },
+}
+
+fn default_density() -> f64 {
+    1.0
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+// angles are in degrees and directions are the way the light travels
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDescription {
+    Point {
+        position: [f64; 3],
+        intensity: [f64; 3],
+    },
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        intensity: [f64; 3],
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    Directional {
+        direction: [f64; 3],
+        irradiance: [f64; 3],
+    },
+}
+
+impl LightDescription {
+    fn to_light(&self) -> Box<dyn Light> {
+        match self {
+            Self::Point {
+                position,
+                intensity,
+            } => Box::new(PointLight::new(vec3(*position), vec3(*intensity))),
+            Self::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => Box::new(SpotLight::new(
+                vec3(*position),
+                vec3(*direction),
+                vec3(*intensity),
+                inner_angle.to_radians(),
+                outer_angle.to_radians(),
+            )),
+            Self::Directional {
+                direction,
+                irradiance,
+            } => Box::new(DirectionalLight::new(vec3(*direction), vec3(*irradiance))),
+        }
+    }
+}
+
+impl ObjectDescription {
+    fn build<F>(
+        &self,
+        scene_path: &Path,
+        directory: &Path,
+        material: &mut F,
+    ) -> Result<Vec<Arc<dyn Hittable>>, SceneError>
+    where
+        F: FnMut(&str) -> Result<Box<dyn Material>, SceneError>,
+    {
+        let invalid = |message: &str| SceneError::Invalid {
+            path: scene_path.to_path_buf(),
+            message: message.to_string(),
+        };
+
+        Ok(match self {
+            Self::Sphere {
+                centre,
+                radius,
+                material: name,
+            } => vec![Arc::new(Sphere::new(
+                vec3(*centre),
+                *radius,
+                material(name)?,
+            ))],
+            Self::Quad {
+                corner,
+                u,
+                v,
+                material: name,
+            } => vec![Arc::new(Quad::new(
+                vec3(*corner),
+                vec3(*u),
+                vec3(*v),
+                material(name)?,
+            )?)],
+            Self::TriangleMesh {
+                positions,
+                indices,
+                normals,
+                uvs,
+                material: name,
+            } => {
+                vec![Arc::new(
+                    TriangleMesh::new(
+                        positions.iter().copied().map(vec3).collect(),
+                        normals.iter().copied().map(vec3).collect(),
+                        uvs.iter().map(|uv| Vec2d::new(uv[0], uv[1])).collect(),
+                        indices.clone(),
+                        material(name)?,
+                    )
+                    .map_err(|error| invalid(&error.to_string()))?,
+                )]
+            }
+            Self::Obj { path } => load_obj(&directory.join(path))?.into_objects(),
+            Self::ConstantMedium { boundary, density } => {
+                if density.is_nan() || *density <= 0.0 {
+                    return Err(invalid("medium density must be positive"));
+                }
+                let name = match boundary.as_ref() {
+                    Self::Sphere { material, .. } | Self::TriangleMesh { material, .. } => material,
+                    _ => return Err(invalid("medium boundary must be a sphere or triangle mesh")),
+                };
+                let phase_function = material(name)?;
+                if !phase_function.is_phase_function() {
+                    return Err(invalid(
+                        "medium material must be `isotropic` or `henyey_greenstein`",
+                    ));
+                }
+
+                let mut shapes = HittableList::new();
+                for object in boundary.build(scene_path, directory, material)? {
+                    shapes.add(object);
+                }
+                vec![Arc::new(ConstantMedium::new(
+                    Arc::new(shapes),
+                    *density,
+                    phase_function,
+                ))]
+            }
+            Self::Moving {
+                object,
+                displacement,
+                time0,
+                time1,
+            } => object
+                .build(scene_path, directory, material)?
+                .into_iter()
+                .map(|object| -> Arc<dyn Hittable> {
+                    Arc::new(LinearMotion::new(
+                        object,
+                        vec3(*displacement),
+                        *time0,
+                        *time1,
+                    ))
+                })
+                .collect(),
+            Self::GridMedium {
+                path,
+                minimum,
+                maximum,
+                density,
+                material: name,
+            } => {
+                let bounds = Aabb::new(vec3(*minimum), vec3(*maximum));
+                if (bounds.maximum - bounds.minimum).min_component() <= 0.0 {
+                    return Err(invalid(
+                        "medium maximum must be above its minimum on every axis",
+                    ));
+                }
+                if density.is_nan() || *density < 0.0 {
+                    return Err(invalid("medium density must not be negative"));
+                }
+                let phase_function = material(name)?;
+                if !phase_function.is_phase_function() {
+                    return Err(invalid(
+                        "medium material must be `isotropic` or `henyey_greenstein`",
+                    ));
+                }
+                vec![Arc::new(GridMedium::new(
+                    bounds,
+                    Arc::new(DensityGrid::load(&directory.join(path))?),
+                    *density,
+                    phase_function,
+                ))]
+            }
+        })
+    }
+}
+
+// a camera at `look_from` needs a distinct point to look at, and an up vector that is not along
+// the view direction to fix its roll
+fn view_error(look_from: Vec3d, look_at: Vec3d, up_vector: Vec3d) -> Option<&'static str> {
+    let forward = look_at - look_from;
+    if forward.length_squared() == 0.0 {
+        return Some("looks at its own position");
+    }
+    // this also catches a zero up vector
+    let cross = forward.normalise().cross(up_vector);
+    if cross.length_squared() <= 1.0e-12 * up_vector.length_squared() {
+        return Some("has an up vector parallel to the view direction");
+    }
+    None
+}
+
+fn vec3(value: [f64; 3]) -> Vec3d {
+    Vec3d::new(value[0], value[1], value[2])
+}
+
+// builds textures, loading every image file only once however many materials use it
+struct TextureLoader<'a> {
+    scene_path: &'a Path,
+    directory: &'a Path,
+    images: HashMap<PathBuf, Arc<Image>>,
+}
+
+impl TextureLoader<'_> {
+    fn load(&mut self, description: &TextureDescription) -> Result<Box<dyn Texture>, SceneError> {
+        Ok(match description {
+            TextureDescription::Colour(colour) => Box::new(SolidColour::new(vec3(*colour))),
+            TextureDescription::Texture(TextureKind::Checker { scale, even, odd }) => {
+                if scale.is_nan() || *scale <= 0.0 {
+                    return Err(SceneError::Invalid {
+                        path: self.scene_path.to_path_buf(),
+                        message: format!("checker scale must be positive, got {scale}"),
+                    });
+                }
+                Box::new(CheckerTexture::new(
+                    *scale,
+                    self.load(even)?,
+                    self.load(odd)?,
+                ))
+            }
+            TextureDescription::Texture(TextureKind::Image { path, wrap }) => {
+                let path = self.directory.join(path);
+                let image = match self.images.get(&path) {
+                    Some(image) => image.clone(),
+                    None => {
+                        let image = Arc::new(Image::load(&path)?);
+                        self.images.insert(path, image.clone());
+                        image
+                    }
+                };
+                Box::new(ImageTexture::new(image, *wrap))
+            }
+        })
+    }
+}
+
+impl MaterialDescription {
+    fn to_material(&self, textures: &mut TextureLoader) -> Result<Box<dyn Material>, SceneError> {
+        Ok(match self {
+            Self::Lambertian { albedo } => {
+                Box::new(Lambertian::with_texture(textures.load(albedo)?))
+            }
+            Self::Metal { albedo, fuzz } => {
+                Box::new(Metal::with_texture(textures.load(albedo)?, *fuzz))
+            }
+            Self::Dielectric { refraction_index } => Box::new(Dielectric::new(*refraction_index)),
+            Self::DiffuseLight { emit } => Box::new(DiffuseLight::new(vec3(*emit))),
+            Self::Isotropic { albedo } => Box::new(Isotropic::new(vec3(*albedo))),
+            Self::HenyeyGreenstein { albedo, g } => {
+                Box::new(HenyeyGreenstein::new(vec3(*albedo), *g))
+            }
+        })
+    }
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&contents, path)
+    }
+
+    // `path` names the scene in errors and anchors relative asset paths
+    pub fn parse(contents: &str, path: &Path) -> Result<Self, SceneError> {
+        let file: SceneFile = toml::from_str(contents).map_err(|source| SceneError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let invalid = |message: String| SceneError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let render = &file.render;
+        if render.aspect_ratio.is_nan() || render.aspect_ratio <= 0.0 {
+            return Err(invalid(format!(
+                "aspect ratio must be positive, got {}",
+                render.aspect_ratio
+            )));
+        }
+        let mut settings = RenderSettings::new(
+            render.width,
+            render.aspect_ratio,
+            render.samples_per_pixel,
+            render.max_depth,
+        );
+        settings.russian_roulette_depth = render.russian_roulette_depth;
+        settings.seed = render.seed;
+        settings.sampler = render.sampler;
+        settings.validate().map_err(invalid)?;
+
+        // relative asset paths are resolved against the scene file
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let aperture_shape = match &file.camera.aperture_shape {
+            ApertureDescription::Circle => ApertureShape::Circle,
+            ApertureDescription::Polygon { blades, rotation } => {
+                if *blades < 3 {
+                    return Err(invalid(format!(
+                        "an aperture polygon needs at least 3 blades, got {blades}"
+                    )));
+                }
+                ApertureShape::Polygon {
+                    blades: *blades,
+                    rotation: rotation.to_radians(),
+                }
+            }
+            ApertureDescription::Mask { path } => {
+                let image = Image::load(&directory.join(path))?;
+                if !image.pixels.iter().any(|pixel| pixel.max_component() > 0.0) {
+                    return Err(invalid("aperture mask must not be black".to_string()));
+                }
+                ApertureShape::Mask(Arc::new(ApertureMask::new(&image)))
+            }
+        };
+
+        let camera_section = &file.camera;
+        let (vfov, aperture, shutter_close) = match &camera_section.physical {
+            Some(physical) => {
+                let controls = [
+                    ("focal length", physical.focal_length),
+                    ("f-number", physical.f_number),
+                    ("ISO", physical.iso),
+                    ("shutter speed", physical.shutter_speed),
+                    ("sensor height", physical.sensor_height),
+                ];
+                for (name, value) in controls {
+                    if value.is_nan() || value <= 0.0 {
+                        return Err(invalid(format!("{name} must be positive, got {value}")));
+                    }
+                }
+                let overridden = [
+                    ("vfov", camera_section.vfov.is_some()),
+                    ("aperture", camera_section.aperture.is_some()),
+                    ("shutter_close", camera_section.shutter_close.is_some()),
+                ];
+                if let Some((name, _)) = overridden.iter().find(|(_, is_set)| *is_set) {
+                    return Err(invalid(format!(
+                        "`{name}` cannot be given with a physical camera, which sets it"
+                    )));
+                }
+
+                settings.exposure = physical.exposure();
+                (
+                    physical.vfov(),
+                    physical.aperture(),
+                    camera_section.shutter_open + physical.shutter_speed,
+                )
+            }
+            None => (
+                camera_section.vfov.unwrap_or(DEFAULT_VFOV),
+                camera_section.aperture.unwrap_or(0.0),
+                camera_section.shutter_close.unwrap_or(0.0),
+            ),
+        };
+
+        let up_vector = vec3(camera_section.up);
+        let (look_from, look_at, animation) = if camera_section.keyframes.is_empty() {
+            let (Some(look_from), Some(look_at)) =
+                (camera_section.look_from, camera_section.look_at)
+            else {
+                return Err(invalid(
+                    "the camera needs `look_from` and `look_at`, or keyframes".to_string(),
+                ));
+            };
+            let (look_from, look_at) = (vec3(look_from), vec3(look_at));
+            if let Some(message) = view_error(look_from, look_at, up_vector) {
+                return Err(invalid(format!("camera {message}")));
+            }
+            (look_from, look_at, None)
+        } else {
+            if camera_section.look_from.is_some() || camera_section.look_at.is_some() {
+                return Err(invalid(
+                    "an animated camera takes `look_from` and `look_at` from its keyframes"
+                        .to_string(),
+                ));
+            }
+            let keyframes = &camera_section.keyframes;
+            if camera_section.physical.is_some()
+                && keyframes.iter().any(|keyframe| keyframe.vfov.is_some())
+            {
+                return Err(invalid(
+                    "keyframes cannot set `vfov` with a physical camera, which sets it".to_string(),
+                ));
+            }
+            let keyframes = keyframes
+                .iter()
+                .map(|keyframe| {
+                    keyframe.to_keyframe(up_vector, vfov, camera_section.focus_distance)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(invalid)?;
+            let first = keyframes[0];
+            let animation = CameraAnimation::new(keyframes).map_err(invalid)?;
+            // the animation replaces these, but they still describe the first keyframe
+            (
+                first.position,
+                first.position - first.orientation.rotate_vector3(Vec3d::Z),
+                Some(animation),
+            )
+        };
+
+        let camera = CameraSettings {
+            look_from,
+            look_at,
+            up_vector,
+            projection: camera_section.projection,
+            vfov,
+            aperture,
+            aperture_shape,
+            focus_distance: camera_section
+                .focus_distance
+                .unwrap_or_else(|| (look_from - look_at).length()),
+            shutter_open: camera_section.shutter_open,
+            shutter_close,
+            animation,
+        };
+        if camera.shutter_open.is_nan() || camera.shutter_open > camera.shutter_close {
+            return Err(invalid(format!(
+                "shutter must open before it closes, got {} to {}",
+                camera.shutter_open, camera.shutter_close
+            )));
+        }
+        match camera.projection {
+            Projection::Orthographic { height } if height.is_nan() || height <= 0.0 => {
+                return Err(invalid(format!(
+                    "orthographic height must be positive, got {height}"
+                )));
+            }
+            Projection::Fisheye { fov } if !(fov > 0.0 && fov <= 360.0) => {
+                return Err(invalid(format!(
+                    "fisheye field of view must be between 0 and 360 degrees, got {fov}"
+                )));
+            }
+            _ => {}
+        }
+
+        let mut textures = TextureLoader {
+            scene_path: path,
+            directory,
+            images: HashMap::new(),
+        };
+        let mut material = |name: &str| match file.materials.get(name) {
+            Some(description) => description.to_material(&mut textures),
+            None => Err(invalid(format!("unknown material `{name}`"))),
+        };
+
+        let environment: Box<dyn Environment> = match &file.environment {
+            EnvironmentDescription::Constant { colour } => {
+                Box::new(ConstantEnvironment::new(vec3(*colour)))
+            }
+            EnvironmentDescription::Gradient { horizon, zenith } => {
+                Box::new(GradientEnvironment::new(vec3(*horizon), vec3(*zenith)))
+            }
+            EnvironmentDescription::Equirectangular {
+                path,
+                rotation,
+                intensity,
+            } => Box::new(EquirectangularEnvironment::new(
+                Arc::new(Image::load(&directory.join(path))?),
+                rotation.to_radians(),
+                *intensity,
+            )),
+            EnvironmentDescription::Sky {
+                elevation,
+                azimuth,
+                turbidity,
+                intensity,
+            } => {
+                // the range the sky model was fitted over
+                if !(1.7..=10.0).contains(turbidity) {
+                    return Err(invalid(format!(
+                        "turbidity must be between 1.7 and 10, got {turbidity}"
+                    )));
+                }
+                Box::new(SkyEnvironment::new(
+                    elevation.to_radians(),
+                    azimuth.to_radians(),
+                    *turbidity,
+                    *intensity,
+                ))
+            }
+        };
+
+        let mut world = HittableList::new();
+        for object in &file.objects {
+            for object in object.build(path, directory, &mut material)? {
+                world.add(object);
+            }
+        }
+
+        for light in &file.lights {
+            let direction = match light {
+                LightDescription::Point { .. } => None,
+                LightDescription::Spot { direction, .. }
+                | LightDescription::Directional { direction, .. } => Some(vec3(*direction)),
+            };
+            if direction.is_some_and(|direction| direction.length_squared() == 0.0) {
+                return Err(invalid("light direction must not be zero".to_string()));
+            }
+        }
+        let lights = file.lights.iter().map(LightDescription::to_light).collect();
+
+        Ok(Self {
+            world,
+            camera,
+            lights,
+            environment,
+            settings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "[camera]\nlook_from = [0.0, 1.0, 5.0]\nlook_at = [0.0, 1.0, 0.0]\n";
+    const OBJECTS: &str = "[materials.grey]\ntype = \"lambertian\"\nalbedo = [0.5, 0.5, 0.5]\n\
+                           [materials.fog]\ntype = \"isotropic\"\nalbedo = [0.9, 0.9, 0.9]\n\
+                           [[objects]]\ntype = \"sphere\"\ncentre = [0.0, 1.0, 0.0]\n\
+                           radius = 1.0\nmaterial = \"grey\"\n";
+
+    fn parse(contents: &str) -> Result<Scene, SceneError> {
+        Scene::parse(contents, Path::new("test.toml"))
+    }
+
+    #[test]
+    fn accepts_valid_scenes() {
+        let scenes = [
+            format!("{CAMERA}{OBJECTS}"),
+            format!("[render]\nwidth = 64\naspect_ratio = 2.0\n{CAMERA}"),
+            format!("{CAMERA}up = [1.0, 0.0, 0.0]\n"),
+            format!("{CAMERA}[camera.physical]\nfocal_length = 50.0\nf_number = 2.8\niso = 100.0\nshutter_speed = 0.01\n"),
+            format!("{CAMERA}[camera.projection]\ntype = \"fisheye\"\nfov = 360.0\n"),
+            "[camera]\n[[camera.keyframes]]\nframe = 0.0\nlook_from = [0.0, 0.0, 5.0]\nlook_at = [0.0, 0.0, 0.0]\n\
+             [[camera.keyframes]]\nframe = 10.0\nlook_from = [5.0, 0.0, 0.0]\nlook_at = [0.0, 0.0, 0.0]\n"
+                .to_string(),
+            format!("{CAMERA}{OBJECTS}[[objects]]\ntype = \"constant_medium\"\ndensity = 0.5\n\
+                     boundary = {{ type = \"sphere\", centre = [0.0, 0.0, 0.0], radius = 1.0, material = \"fog\" }}\n"),
+        ];
+        for scene in scenes {
+            if let Err(error) = parse(&scene) {
+                panic!("{scene}\nrejected with {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_scenes() {
+        let physical = "[camera.physical]\nfocal_length = 50.0\nf_number = 2.8\niso = 100.0\n\
+                        shutter_speed = 0.01\n";
+        let keyframe = |frame: f64| {
+            format!(
+                "[[camera.keyframes]]\nframe = {frame:?}\nlook_from = [0.0, 0.0, 5.0]\n\
+                 look_at = [0.0, 0.0, 0.0]\n"
+            )
+        };
+        let cases = [
+            (
+                "[camera]\nlook_from = [1.0, 2.0, 3.0]\nlook_at = [1.0, 2.0, 3.0]\n".to_string(),
+                "looks at its own position",
+            ),
+            (
+                "[camera]\nlook_from = [0.0, 5.0, 0.0]\nlook_at = [0.0, 0.0, 0.0]\n".to_string(),
+                "parallel to the view direction",
+            ),
+            (format!("{CAMERA}up = [0.0, 0.0, 0.0]\n"), "parallel to the view direction"),
+            ("[camera]\nlook_from = [0.0, 1.0, 5.0]\n".to_string(), "needs `look_from` and `look_at`"),
+            (format!("{CAMERA}vfov = 30.0\n{physical}"), "`vfov` cannot be given with a physical camera"),
+            (format!("{CAMERA}aperture = 0.1\n{physical}"), "`aperture` cannot be given with a physical camera"),
+            (format!("[render]\naspect_ratio = 0.0\n{CAMERA}"), "aspect ratio must be positive"),
+            (format!("[render]\nwidth = 1\n{CAMERA}"), "at least 2x2"),
+            (format!("[render]\nwidth = 70000\naspect_ratio = 1.0\n{CAMERA}"), "larger than the limit"),
+            (format!("[render]\nsamples_per_pixel = 0\n{CAMERA}"), "samples per pixel"),
+            (format!("{CAMERA}shutter_open = 1.0\nshutter_close = 0.5\n"), "shutter must open before it closes"),
+            (format!("{CAMERA}[camera.projection]\ntype = \"orthographic\"\nheight = 0.0\n"), "orthographic height"),
+            (format!("{CAMERA}[camera.projection]\ntype = \"fisheye\"\nfov = 400.0\n"), "fisheye field of view"),
+            (format!("{CAMERA}[camera.aperture_shape]\ntype = \"polygon\"\nblades = 2\n"), "at least 3 blades"),
+            (
+                format!("[camera]\n{}{}", keyframe(10.0), keyframe(5.0)),
+                "increasing frame order",
+            ),
+            (
+                format!("{CAMERA}{}", keyframe(0.0)),
+                "takes `look_from` and `look_at` from its keyframes",
+            ),
+            (
+                format!("{CAMERA}[[objects]]\ntype = \"sphere\"\ncentre = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"missing\"\n"),
+                "unknown material `missing`",
+            ),
+            (
+                format!("{CAMERA}{OBJECTS}[[objects]]\ntype = \"constant_medium\"\ndensity = 0.5\n\
+                         boundary = {{ type = \"sphere\", centre = [0.0, 0.0, 0.0], radius = 1.0, material = \"grey\" }}\n"),
+                "must be `isotropic` or `henyey_greenstein`",
+            ),
+            (
+                format!("{CAMERA}{OBJECTS}[[objects]]\ntype = \"triangle_mesh\"\npositions = [[0.0, 0.0, 0.0]]\n\
+                         indices = [[0, 1, 2]]\nmaterial = \"grey\"\n"),
+                "mesh index out of range",
+            ),
+            (
+                format!("{CAMERA}[materials.board]\ntype = \"lambertian\"\n\
+                         albedo = {{ type = \"checker\", scale = 0.0, even = [1.0, 1.0, 1.0], odd = [0.0, 0.0, 0.0] }}\n\
+                         [[objects]]\ntype = \"sphere\"\ncentre = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"board\"\n"),
+                "checker scale must be positive",
+            ),
+            (
+                format!("{CAMERA}[[lights]]\ntype = \"directional\"\ndirection = [0.0, 0.0, 0.0]\nirradiance = [1.0, 1.0, 1.0]\n"),
+                "light direction must not be zero",
+            ),
+        ];
+
+        for (scene, expected) in cases {
+            match parse(&scene) {
+                Err(SceneError::Invalid { message, .. }) => {
+                    assert!(
+                        message.contains(expected),
+                        "{scene}\ngave `{message}`, not `{expected}`"
+                    );
+                }
+                Err(error) => panic!("{scene}\nfailed to parse: {error}"),
+                Ok(_) => panic!("{scene}\nwas accepted"),
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::*;
+
+// `name` is a built-in scene, an OBJ model or a TOML scene file; `seed` lays out the random world
+pub fn load_scene(name: Option<&str>, seed: u64) -> Result<Scene, SceneError> {
+    match name {
+        None | Some("random_world") => Ok(random_world(seed)),
+        Some("cornell_box") => cornell_box(),
+        Some("cornell_meshes") => cornell_meshes(),
+        Some(path) if path.ends_with(".obj") => Ok(obj_scene(Path::new(path))?),
+        Some(path) => Scene::load(Path::new(path)),
+    }
+}
+
+pub fn random_world(seed: u64) -> Scene {
+    seed_thread_rng(seed);
+    let mut world = HittableList::new();
+
+    let ground_material = Box::new(Lambertian::new(Vec3d::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for i in -10..=10 {
+        for j in -10..=10 {
+            let center = Vec3d::new(
+                i as f64 + 0.9 * gen_random(),
+                0.2,
+                j as f64 + 0.9 * gen_random(),
+            );
+
+            if (center - Vec3d::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let choose_mat = gen_random();
+                let sphere_material: Box<dyn Material>;
+
+                if choose_mat < 0.8 {
+                    let albedo = Vec3d::new(
+                        gen_random() * gen_random(),
+                        gen_random() * gen_random(),
+                        gen_random() * gen_random(),
+                    );
+                    sphere_material = Box::new(Lambertian::new(albedo));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                } else if choose_mat < 0.95 {
+                    let albedo = Vec3d::new(gen_random(), gen_random(), gen_random());
+                    let fuzz = gen_random_range(0.0, 0.5);
+                    sphere_material = Box::new(Metal::new(albedo, fuzz));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    sphere_material = Box::new(Dielectric::new(1.5));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(Dielectric::new(1.5)),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(Lambertian::new(Vec3d::new(0.4, 0.2, 0.1))),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(Metal::new(Vec3d::new(0.7, 0.6, 0.5), 0.0)),
+    )));
+
+    let look_from = Vec3d::new(13.0, 2.0, 3.0);
+    let look_at = Vec3d::new(0.0, 0.0, 0.0);
+    let up_vector = Vec3d::new(0.0, 1.0, 0.0);
+    let focus_distance = 10.0;
+    let aperture = 0.1;
+
+    let camera = CameraSettings {
+        look_from,
+        look_at,
+        up_vector,
+        projection: Projection::Perspective,
+        vfov: 20.0,
+        aperture,
+        aperture_shape: ApertureShape::Circle,
+        focus_distance,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+        animation: None,
+    };
+
+    Scene {
+        world,
+        camera,
+        lights: vec![],
+        environment: Box::new(GradientEnvironment::default()),
+        settings: RenderSettings {
+            seed,
+            ..RenderSettings::default()
+        },
+    }
+}
+
+// a closed room lit only by the ceiling panel, with the front wall behind the camera and no sky
+pub fn cornell_box() -> Result<Scene, SceneError> {
+    let mut world = cornell_room()?;
+
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(190.0, 90.0, 190.0),
+        90.0,
+        Box::new(Dielectric::new(1.5)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Vec3d::new(370.0, 120.0, 370.0),
+        120.0,
+        Box::new(Lambertian::new(Colour::new(0.73, 0.73, 0.73))),
+    )));
+    world.add(Arc::new(cornell_lamp()));
+
+    Ok(cornell_scene(world))
+}
+
+// the same room furnished with triangle meshes: a tessellated glass sphere and a turned box
+pub fn cornell_meshes() -> Result<Scene, SceneError> {
+    let mut world = cornell_room()?;
+
+    world.add(Arc::new(uv_sphere_mesh(
+        Vec3d::new(190.0, 90.0, 190.0),
+        90.0,
+        24,
+        12,
+        Box::new(Dielectric::new(1.5)),
+    )?));
+    world.add(Arc::new(box_mesh(
+        Vec3d::new(265.0, 0.0, 295.0),
+        Vec3d::new(165.0, 330.0, 165.0),
+        15.0,
+        Box::new(Lambertian::new(Colour::new(0.73, 0.73, 0.73))),
+    )?));
+    world.add(Arc::new(cornell_lamp()));
+
+    Ok(cornell_scene(world))
+}
+
+fn cornell_room() -> Result<HittableList, QuadError> {
+    let mut world = HittableList::new();
+
+    let red = || Box::new(Lambertian::new(Colour::new(0.65, 0.05, 0.05)));
+    let white = || Box::new(Lambertian::new(Colour::new(0.73, 0.73, 0.73)));
+    let green = || Box::new(Lambertian::new(Colour::new(0.12, 0.45, 0.15)));
+    let light = Box::new(DiffuseLight::new(Colour::new(15.0, 15.0, 15.0)));
+
+    let depth = 555.0 + 801.0;
+    let front = -801.0;
+
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(555.0, 0.0, front),
+        Vec3d::new(0.0, 555.0, 0.0),
+        Vec3d::new(0.0, 0.0, depth),
+        green(),
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(0.0, 0.0, front),
+        Vec3d::new(0.0, 555.0, 0.0),
+        Vec3d::new(0.0, 0.0, depth),
+        red(),
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(343.0, 554.0, 332.0),
+        Vec3d::new(-130.0, 0.0, 0.0),
+        Vec3d::new(0.0, 0.0, -105.0),
+        light,
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(0.0, 0.0, front),
+        Vec3d::new(555.0, 0.0, 0.0),
+        Vec3d::new(0.0, 0.0, depth),
+        white(),
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(555.0, 555.0, 555.0),
+        Vec3d::new(-555.0, 0.0, 0.0),
+        Vec3d::new(0.0, 0.0, -depth),
+        white(),
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(0.0, 0.0, 555.0),
+        Vec3d::new(555.0, 0.0, 0.0),
+        Vec3d::new(0.0, 555.0, 0.0),
+        white(),
+    )?));
+    world.add(Arc::new(Quad::new(
+        Vec3d::new(0.0, 0.0, front),
+        Vec3d::new(555.0, 0.0, 0.0),
+        Vec3d::new(0.0, 555.0, 0.0),
+        white(),
+    )?));
+
+    Ok(world)
+}
+
+// a small warm light hanging below the ceiling panel
+fn cornell_lamp() -> Sphere {
+    Sphere::new(
+        Vec3d::new(278.0, 460.0, 278.0),
+        20.0,
+        Box::new(DiffuseLight::new(Colour::new(4.0, 2.0, 0.5))),
+    )
+}
+
+fn cornell_scene(world: HittableList) -> Scene {
+    let look_from = Vec3d::new(278.0, 278.0, -800.0);
+    let look_at = Vec3d::new(278.0, 278.0, 0.0);
+    let up_vector = Vec3d::new(0.0, 1.0, 0.0);
+
+    let camera = CameraSettings {
+        look_from,
+        look_at,
+        up_vector,
+        projection: Projection::Perspective,
+        vfov: 40.0,
+        aperture: 0.0,
+        aperture_shape: ApertureShape::Circle,
+        focus_distance: 10.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+        animation: None,
+    };
+
+    Scene {
+        world,
+        camera,
+        lights: vec![],
+        environment: Box::new(ConstantEnvironment::new(Colour::new(0.0, 0.0, 0.0))),
+        settings: RenderSettings::default(),
+    }
+}
+
+// frames the loaded model from slightly above, looking at the centre of its bounds
+pub fn obj_scene(path: &Path) -> Result<Scene, ObjError> {
+    let world = load_obj(path)?;
+
+    let bounding_box = world.bounding_box();
+    let look_at = bounding_box.centroid();
+    let radius = ((bounding_box.maximum - bounding_box.minimum).length() / 2.0).max(1.0e-3);
+    let look_from = look_at + Vec3d::new(0.0, 0.3, 1.0).normalise() * radius * 3.0;
+    let up_vector = Vec3d::new(0.0, 1.0, 0.0);
+
+    let camera = CameraSettings {
+        look_from,
+        look_at,
+        up_vector,
+        projection: Projection::Perspective,
+        vfov: 40.0,
+        aperture: 0.0,
+        aperture_shape: ApertureShape::Circle,
+        focus_distance: radius * 3.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+        animation: None,
+    };
+
+    Ok(Scene {
+        world,
+        camera,
+        lights: vec![],
+        environment: Box::new(GradientEnvironment::default()),
+        settings: RenderSettings::default(),
+    })
+}
+
+// an axis aligned box resting on `base`, rotated about its vertical centre line
+fn box_mesh(
+    base: Vec3d,
+    size: Vec3d,
+    rotation_degrees: f64,
+    material: Box<dyn Material>,
+) -> Result<TriangleMesh, MeshError> {
+    let (sin, cos) = rotation_degrees.to_radians().sin_cos();
+    let half = size / 2.0;
+    let centre = base + Vec3d::new(half.x, 0.0, half.z);
+
+    let mut positions = vec![];
+    for &y in &[0.0, size.y] {
+        for &(x, z) in &[
+            (-half.x, -half.z),
+            (half.x, -half.z),
+            (half.x, half.z),
+            (-half.x, half.z),
+        ] {
+            positions.push(centre + Vec3d::new(cos * x + sin * z, y, -sin * x + cos * z));
+        }
+    }
+
+    let faces = [
+        [0, 1, 2, 3],
+        [4, 7, 6, 5],
+        [0, 4, 5, 1],
+        [1, 5, 6, 2],
+        [2, 6, 7, 3],
+        [3, 7, 4, 0],
+    ];
+    let indices = faces
+        .iter()
+        .flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]])
+        .collect();
+
+    TriangleMesh::new(positions, vec![], vec![], indices, material)
+}
+
+fn uv_sphere_mesh(
+    centre: Vec3d,
+    radius: f64,
+    segments: usize,
+    rings: usize,
+    material: Box<dyn Material>,
+) -> Result<TriangleMesh, MeshError> {
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+
+    for ring in 0..=rings {
+        let v = ring as f64 / rings as f64;
+        let theta = v * std::f64::consts::PI;
+        for segment in 0..=segments {
+            let u = segment as f64 / segments as f64;
+            let phi = u * 2.0 * std::f64::consts::PI;
+            let normal = Vec3d::new(
+                theta.sin() * phi.cos(),
+                -theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            positions.push(centre + normal * radius);
+            normals.push(normal);
+            uvs.push(Vec2d::new(u, v));
+        }
+    }
+
+    let mut indices = vec![];
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = ring * (segments + 1) + segment;
+            let b = a + segments + 1;
+            indices.push([a, b, a + 1]);
+            indices.push([a + 1, b, b + 1]);
+        }
+    }
+
+    TriangleMesh::new(positions, normals, uvs, indices, material)
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::*;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: Vec3d) -> Colour;
+}
+
+pub struct SolidColour {
+    colour: Colour,
+}
+
+impl SolidColour {
+    pub const fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+}
+
+impl Texture for SolidColour {
+    fn value(&self, _u: f64, _v: f64, _point: Vec3d) -> Colour {
+        self.colour
+    }
+}
+
+pub struct CheckerTexture {
+    inverse_scale: f64,
+    even: Box<dyn Texture>,
+    odd: Box<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Box<dyn Texture>, odd: Box<dyn Texture>) -> Self {
+        Self {
+            inverse_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: Vec3d) -> Colour {
+        let cell = (self.inverse_scale * point.x).floor()
+            + (self.inverse_scale * point.y).floor()
+            + (self.inverse_scale * point.z).floor();
+
+        if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+}
+
+impl WrapMode {
+    pub fn apply(self, coordinate: isize, size: usize) -> usize {
+        match self {
+            Self::Repeat => coordinate.rem_euclid(size as isize) as usize,
+            Self::Clamp => coordinate.clamp(0, size as isize - 1) as usize,
+        }
+    }
+}
+
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>, wrap: WrapMode) -> Self {
+        Self { image, wrap }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: Vec3d) -> Colour {
+        self.image.bilinear(u, v, self.wrap, self.wrap)
+    }
+}
@@ -1,15 +1,43 @@
-use rand::Rng;
+use std::cell::RefCell;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use spyder_math::Vec3d;
 
-pub fn random_new_vec3d() -> Vec3d {
-    let lower: f64 = -1.0;
-    let upper: f64 = 1.0;
-    Vec3d::new(
-        rand::thread_rng().gen_range(lower..upper),
-        rand::thread_rng().gen_range(lower..upper),
-        rand::thread_rng().gen_range(lower..upper),
+// seeded from the render seed before use, so no output depends on the order threads run in
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::seed_from_u64(0));
+}
+
+pub fn seed_thread_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn gen_random() -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(0.0..1.0))
+}
+
+pub fn gen_random_range(low: f64, high: f64) -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(low..high))
+}
+
+// splitmix64 finaliser, used to derive independent seeds from a base seed
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// two unit vectors that complete an orthonormal basis with the unit vector `w` (Duff et al. 2017)
+pub fn orthonormal_basis(w: Vec3d) -> (Vec3d, Vec3d) {
+    let sign = 1.0f64.copysign(w.z);
+    let a = -1.0 / (sign + w.z);
+    let b = w.x * w.y * a;
+    (
+        Vec3d::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+        Vec3d::new(b, sign + w.y * w.y * a, -w.y),
     )
-    .normalise()
 }
 
 pub fn reflect_on_normal(incident: Vec3d, normal: Vec3d) -> Vec3d {